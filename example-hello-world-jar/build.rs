@@ -1,3 +1,11 @@
 fn main() {
-    jerk::metabuild();
+    // tests/junit.rs is #[ignore]d unless JUnit is available
+    println!("cargo:rerun-if-env-changed=JERK_EXAMPLE_JUNIT_CLASSPATH");
+    println!("cargo:rustc-check-cfg=cfg(jerk_example_junit)");
+    if std::env::var_os("JERK_EXAMPLE_JUNIT_CLASSPATH").map_or(false, |v| !v.is_empty()) { println!("cargo:rustc-cfg=jerk_example_junit"); }
+
+    jerk::Metabuild {
+        javadoc_jar:        true,
+        ..jerk::Metabuild::default()
+    }.run();
 }
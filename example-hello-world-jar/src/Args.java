@@ -0,0 +1,34 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Args {
+    public static int add(int a, int b) { return a + b; }
+    public static boolean not(boolean b) { return !b; }
+    public static double half(double d) { return d / 2; }
+    public static String greet(String name) { return "Hello, " + name + "!"; }
+    public static String nothing() { return null; }
+    public static char upper(char c) { return Character.toUpperCase(c); }
+    public static char[] chars(String s) { return s.toCharArray(); }
+    public static String string(char[] c) { return new String(c); }
+
+    public static long sum(int[] values) {
+        long sum = 0;
+        for (int value : values) sum += value;
+        return sum;
+    }
+
+    public static String[] split(String s) { return s.split(","); }
+    public static String join(String[] s) { return String.join(",", s); }
+    public static byte[] bytes(String s) { return s.getBytes(java.nio.charset.StandardCharsets.UTF_8); }
+    public static String property(String name) { return System.getProperty(name); }
+    public static String threadName() { return Thread.currentThread().getName(); }
+    public static boolean isDaemon() { return Thread.currentThread().isDaemon(); }
+
+    @com.maulingmonkey.jerk.JerkTest
+    public static void adds() {
+        check(3, 1, 2);
+    }
+
+    public static void check(int expected, int a, int b) {
+        assert expected == a + b : expected + " != " + a + " + " + b;
+    }
+}
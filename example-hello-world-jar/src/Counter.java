@@ -0,0 +1,6 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Counter {
+    private static int count;
+    public static int increment() { return ++count; }
+}
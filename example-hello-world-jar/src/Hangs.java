@@ -0,0 +1,11 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Hangs {
+    public static void sleep() throws InterruptedException {
+        Thread.sleep(Long.MAX_VALUE);
+    }
+
+    public static boolean isInterrupted() {
+        return Thread.currentThread().isInterrupted();
+    }
+}
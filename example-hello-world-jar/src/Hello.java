@@ -0,0 +1,14 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Hello {
+    public static void main(String[] args) {
+        String name = args.length > 0 ? args[0] : "world";
+        System.out.println("Hello, " + name + "!");
+        System.exit(Integer.getInteger("hello.exit", 0));
+    }
+
+    public static void exit() {
+        System.out.println("Goodbye!");
+        System.exit(3);
+    }
+}
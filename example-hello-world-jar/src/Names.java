@@ -0,0 +1,8 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+// Names$Inner and Names_Inner both become `Names_Inner` in Rust, so the generated #[test]s only build if they're de-duplicated
+public class Names {
+    public static class Inner {
+        public static void test() {}
+    }
+}
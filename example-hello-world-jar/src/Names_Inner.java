@@ -0,0 +1,5 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Names_Inner {
+    public static void test() {}
+}
@@ -0,0 +1,7 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+// Replaced by java9/Platform.java when running on Java 9+ (see META-INF/versions/9/ in the multi-release jar)
+public class Platform {
+    public static String release() { return "8"; }
+    public static long pid() { return -1; }
+}
@@ -0,0 +1,8 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Prints {
+    public static void hello() {
+        System.out.println("Hello from System.out, caf\u00e9");
+        System.err.println("Hello from System.err");
+    }
+}
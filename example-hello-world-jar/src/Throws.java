@@ -0,0 +1,15 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Throws {
+    public static void chained() {
+        try {
+            inner();
+        } catch (IllegalArgumentException e) {
+            throw new IllegalStateException("outer", e);
+        }
+    }
+
+    private static void inner() {
+        throw new IllegalArgumentException("inner");
+    }
+}
@@ -0,0 +1,6 @@
+package com.maulingmonkey.jerk.example_hello_world_jar;
+
+public class Platform {
+    public static String release() { return "9"; }
+    public static long pid() { return ProcessHandle.current().pid(); }
+}
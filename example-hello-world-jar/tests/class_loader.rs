@@ -0,0 +1,24 @@
+// Separate test binary, since the class loader option applies to the whole process
+
+fn configure() {
+    jerk::test::configure(jerk::test::TestVmOptions {
+        class_loader: jerk::test::TestClassLoader::PerTest,
+        ..Default::default()
+    }).unwrap();
+}
+
+// Both tests would see a count of 3 and 4 in one of them if Counter's statics were shared
+
+#[test] fn fresh_statics_a() {
+    configure();
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Counter", "increment" => i32), 1);
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Counter", "increment" => i32), 2);
+    jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Adder", "test"); // native methods
+}
+
+#[test] fn fresh_statics_b() {
+    configure();
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Counter", "increment" => i32), 1);
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Counter", "increment" => i32), 2);
+    jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Adder", "test"); // native methods
+}
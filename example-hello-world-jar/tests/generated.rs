@@ -0,0 +1 @@
+include!(env!("JERK_BUILD_TESTS"));
@@ -0,0 +1,50 @@
+// Separate test binary, since JUnit must be on the class path of the first Java VM created in the process.
+// JUnit isn't bundled:  set JERK_EXAMPLE_JUNIT_CLASSPATH to e.g. junit-4.13.2.jar + hamcrest-core-1.3.jar to run this (CI does.)
+// Otherwise build.rs leaves it #[ignore]d, so it shows up as ignored instead of passing without testing anything.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Compiled here rather than living in src/, since the crate's own sources must build without JUnit
+const ADDER_TEST : &str = r#"package com.maulingmonkey.jerk.example_hello_world_jar;
+
+import static org.hamcrest.CoreMatchers.is;
+import static org.junit.Assert.*;
+import org.junit.Test;
+
+public class AdderTest {
+    @Test public void adds()    { assertThat(Args.add(1, 2), is(3)); }
+    @Test public void fails()   { assertEquals(4, Args.add(1, 2)); }
+}
+"#;
+
+#[test] #[cfg_attr(not(jerk_example_junit), ignore = "JERK_EXAMPLE_JUNIT_CLASSPATH not set")] fn junit4() {
+    let junit = std::env::var_os("JERK_EXAMPLE_JUNIT_CLASSPATH").filter(|junit| !junit.is_empty()).expect("JERK_EXAMPLE_JUNIT_CLASSPATH not set");
+    let junit = std::env::split_paths(&junit).collect::<Vec<_>>();
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("junit4");
+    let source = dir.join("AdderTest.java");
+    let classes = dir.join("classes");
+    std::fs::create_dir_all(&classes).unwrap();
+    std::fs::write(&source, ADDER_TEST).unwrap();
+
+    let compile_classpath = std::env::join_paths(junit.iter().map(PathBuf::as_path).chain(std::iter::once(Path::new(env!("JERK_BUILD_JAR"))))).unwrap();
+    let status = Command::new(Path::new(env!("JAVA_HOME")).join("bin").join("javac"))
+        .arg("-cp").arg(compile_classpath)
+        .arg("-d").arg(&classes)
+        .arg(&source)
+        .status().unwrap();
+    assert!(status.success(), "javac AdderTest.java failed: {:?}", status);
+
+    jerk::test::configure(jerk::test::TestVmOptions {
+        class_paths: junit.into_iter().chain(std::iter::once(classes)).collect(),
+        ..Default::default()
+    }).unwrap();
+
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    jerk::run_junit_test!(package, "AdderTest", "adds");
+    let failure = std::panic::catch_unwind(|| jerk::run_junit_test!(package, "AdderTest", "fails")).unwrap_err();
+    let failure = failure.downcast_ref::<String>().unwrap();
+    assert!(failure.contains("java.lang.AssertionError"), "{}", failure);
+    assert!(failure.contains("expected:<4> but was:<3>"), "{}", failure);
+}
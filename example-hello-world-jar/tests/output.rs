@@ -0,0 +1,21 @@
+use std::process::{Command, Output};
+
+#[test] fn prints() {
+    jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Prints", "hello");
+}
+
+#[test] fn captured() {
+    let run = |args: &[&str]| -> Output { Command::new(std::env::current_exe().unwrap()).args(["prints", "--exact"]).args(args).output().unwrap() };
+
+    // libtest swallows the output of passing tests...
+    let captured = run(&[]);
+    assert!(captured.status.success());
+    assert!(!String::from_utf8_lossy(&captured.stdout).contains("Hello from System.out"));
+    assert!(!String::from_utf8_lossy(&captured.stderr).contains("Hello from System.err"));
+
+    // ...unless asked not to
+    let nocapture = run(&["--nocapture"]);
+    assert!(nocapture.status.success());
+    assert!(String::from_utf8_lossy(&nocapture.stdout).contains("Hello from System.out, caf\u{E9}\n"));
+    assert!(String::from_utf8_lossy(&nocapture.stderr).contains("Hello from System.err\n"));
+}
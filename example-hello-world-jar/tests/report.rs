@@ -0,0 +1,31 @@
+use std::process::Command;
+
+#[test] fn java_tests() {
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    jerk::run_test!(package, "Adder", "test");
+    assert_eq!(jerk::run_test!(package, "Args", "greet", "<report>" => String), "Hello, <report>!");
+    assert!(std::panic::catch_unwind(|| jerk::run_test!(package, "Throws", "chained")).is_err());
+}
+
+#[test] fn report() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("jerk-report-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let exe = std::env::current_exe().unwrap();
+    let output = Command::new(&exe)
+        .args(["java_tests", "--exact"])
+        .env("JERK_TEST_REPORT", &dir)
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+
+    // Named after the test binary, so other test binaries writing reports to the same directory don't overwrite it
+    let path = dir.join(format!("{}.xml", exe.file_stem().unwrap().to_string_lossy()));
+    let xml = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n"), "{}", xml);
+    assert!(xml.contains("<testsuite name=\"com.maulingmonkey.jerk.example_hello_world_jar.Adder\" tests=\"1\" failures=\"0\" errors=\"0\""), "{}", xml);
+    assert!(xml.contains("<testcase classname=\"com.maulingmonkey.jerk.example_hello_world_jar.Adder\" name=\"test\" time=\""), "{}", xml);
+    assert!(xml.contains("<testcase classname=\"com.maulingmonkey.jerk.example_hello_world_jar.Args\" name=\"greet\" time=\""), "{}", xml);
+    assert!(xml.contains("<testsuite name=\"com.maulingmonkey.jerk.example_hello_world_jar.Throws\" tests=\"1\" failures=\"0\" errors=\"1\""), "{}", xml);
+    assert!(xml.contains("<error message=\"outer\" type=\"java.lang.IllegalStateException\">java.lang.IllegalStateException: outer\n\tat com.maulingmonkey.jerk.example_hello_world_jar.Throws.chained(Throws.java:8)\n"), "{}", xml);
+    assert!(xml.ends_with("</testsuites>\n"), "{}", xml);
+}
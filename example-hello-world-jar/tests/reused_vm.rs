@@ -0,0 +1,14 @@
+// Separate test binary, since the Java VM must be created before any run_test!
+
+#[test] fn reused_vm() {
+    // Simulate a Java host or another library having already started a Java VM without the crate's .jar on its class path
+    let jvm = jerk::jvm::Library::get().unwrap();
+    let _vm = jvm.create_vm_with(&jerk::jvm::InitArgs {
+        system_properties:  vec![("jerk.host".into(), "reused".into())],
+        ..Default::default()
+    }).unwrap();
+
+    jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Adder", "test"); // native methods
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Args", "add", 1, 2 => i32), 3);
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Args", "property", "jerk.host" => String), "reused");
+}
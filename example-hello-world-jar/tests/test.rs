@@ -1,4 +1,172 @@
+use std::path::Path;
+use std::time::Duration;
+
 #[test] fn test() {
     jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Adder",  "test");
     jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Global", "test");
 }
+
+#[test] fn args() {
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    assert_eq!(jerk::run_test!(package, "Args", "add", 1, 2 => i32), 3);
+    assert_eq!(jerk::run_test!(package, "Args", "not", true => bool), false);
+    assert_eq!(jerk::run_test!(package, "Args", "half", 3.0 => f64), 1.5);
+    assert_eq!(jerk::run_test!(package, "Args", "greet", "jerk" => String), "Hello, jerk!");
+    assert_eq!(jerk::run_test!(package, "Args", "nothing" => Option<String>), None);
+    assert_eq!(jerk::run_test!(package, "Args", "sum", vec![1, 2, 3] => i64), 6);
+    assert_eq!(jerk::run_test!(package, "Args", "split", "a,b,c" => Vec<String>), ["a", "b", "c"]);
+    assert_eq!(jerk::run_test!(package, "Args", "join", ["a", "b", "c"] => String), "a,b,c");
+    assert_eq!(jerk::run_test!(package, "Args", "bytes", "\u{1F980}" => Vec<i8>), [0xF0u8 as i8, 0x9F_u8 as i8, 0xA6_u8 as i8, 0x80_u8 as i8]);
+    assert_eq!(jerk::run_test!(package, "Args", "upper", '\u{E9}' => char), '\u{C9}');
+    assert_eq!(jerk::run_test!(package, "Args", "upper", 0x61u16 => u16), 0x41);
+    assert_eq!(jerk::run_test!(package, "Args", "chars", "h\u{1F980}" => Vec<u16>), "h\u{1F980}".encode_utf16().collect::<Vec<_>>());
+    assert_eq!(jerk::run_test!(package, "Args", "chars", "h\u{E9}" => Vec<char>), ['h', '\u{E9}']);
+    assert_eq!(jerk::run_test!(package, "Args", "string", ['h', '\u{E9}'] => String), "h\u{E9}");
+    jerk::run_test!(package, "Args", "check", 3, 1, 2);
+}
+
+#[test] fn errors() {
+    use jerk::test::JavaTestError;
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    assert_eq!(jerk::try_run_test!(package, "Args", "add", 1, 2 => i32).unwrap(), 3);
+    assert!(matches!(jerk::try_run_test!(package, "Missing", "test"),           Err(JavaTestError::ClassNotFound(_))));
+    assert!(matches!(jerk::try_run_test!(package, "Args", "missing"),           Err(JavaTestError::MethodNotFound(_))));
+    assert!(matches!(jerk::try_run_test!(package, "Args", "add", 1 => i32),     Err(JavaTestError::SignatureMismatch(_))));
+    assert!(matches!(jerk::try_run_test!(package, "Args", "upper", '\u{1F980}' => char), Err(JavaTestError::Unknown(_)))); // not a single UTF-16 unit
+    assert!(matches!(jerk::try_run_test!(package, "Args", "chars", "\u{1F980}" => Vec<char>), Err(JavaTestError::Unknown(_))));
+    match jerk::try_run_test!(package, "Throws", "chained") {
+        Err(JavaTestError::Exception(exception)) => assert_eq!(exception.class, "java.lang.IllegalStateException"),
+        other => panic!("expected an exception, got {:?}", other),
+    }
+}
+
+#[test] fn threads() {
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    // Test threads are attached as daemons named after the #[test]
+    assert_eq!(jerk::run_test!(package, "Args", "threadName" => String), "threads");
+    assert_eq!(jerk::run_test!(package, "Args", "isDaemon" => bool), true);
+
+    std::thread::spawn(move || {
+        let guard = jerk::test::vm().unwrap().attach_current_thread(Some("worker")).unwrap();
+        assert!(guard.detaches_on_drop());
+        assert_eq!(jerk::run_test!(package, "Args", "threadName" => String), "worker");
+        assert_eq!(jerk::run_test!(package, "Args", "isDaemon" => bool), false);
+        drop(guard);
+        assert!(jerk::test::with_env(|env| !env.is_null()).unwrap()); // attaches only for the duration of the closure
+    }).join().unwrap();
+}
+
+#[test] fn timeout() {
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    let panic = std::panic::catch_unwind(|| jerk::run_test!(timeout = Duration::from_millis(500); package, "Hangs", "sleep")).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("Hangs.sleep() timed out after 500ms"), "{}", message);
+    assert!(message.contains(" TIMED_WAITING\n"), "{}", message);
+    assert!(message.contains("\tat com.maulingmonkey.jerk.example_hello_world_jar.Hangs.sleep(Hangs.java:5)"), "{}", message);
+
+    // The VM is still usable afterwards, and the test thread is no longer interrupted
+    assert_eq!(jerk::run_test!(timeout = Duration::from_secs(60); package, "Hangs", "isInterrupted" => bool), false);
+}
+
+#[test] #[should_panic(expected = "java.lang.IllegalStateException: outer\n\tat com.maulingmonkey.jerk.example_hello_world_jar.Throws.chained(Throws.java:8)\nCaused by: java.lang.IllegalArgumentException: inner\n\tat com.maulingmonkey.jerk.example_hello_world_jar.Throws.inner(Throws.java:13)")]
+fn exception() {
+    jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Throws", "chained");
+}
+
+#[test] fn isolated() {
+    jerk::run_test_isolated!("com.maulingmonkey.jerk.example_hello_world_jar", "Adder", "test");
+}
+
+#[test] #[should_panic(expected = "Hello.exit() failed in isolated test process: exited with code 3")] fn isolated_exit() {
+    jerk::run_test_isolated!("com.maulingmonkey.jerk.example_hello_world_jar", "Hello", "exit");
+}
+
+/// The `#[test]` to re-run comes from libtest's name for the thread, not the function the macro is expanded in
+fn run_adder_isolated() {
+    jerk::run_test_isolated!("com.maulingmonkey.jerk.example_hello_world_jar", "Adder", "test");
+}
+
+#[test] fn isolated_through_helper() {
+    run_adder_isolated();
+}
+
+fn run_exit_isolated(test: &str) {
+    jerk::run_test_isolated!(name = test; "com.maulingmonkey.jerk.example_hello_world_jar", "Hello", "exit");
+}
+
+#[test] #[should_panic(expected = "Hello.exit() failed in isolated test process: exited with code 3")] fn isolated_exit_through_helper() {
+    run_exit_isolated("isolated_exit_through_helper");
+}
+
+#[test] fn isolated_single_threaded() {
+    // With --test-threads=1, older versions of libtest run tests on the main thread instead of one named after the test
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["isolated", "isolated_exit", "isolated_exit_through_helper", "--exact", "--test-threads=1"])
+        .output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("3 passed"), "{}", stdout);
+}
+
+#[test] fn multi_release() {
+    let jar_exe = Path::new(env!("JAVA_HOME")).join("bin").join("jar");
+    let jar = Path::new(env!("JERK_BUILD_JAR"));
+    let list = std::process::Command::new(&jar_exe).arg("tf").arg(jar).output().unwrap();
+    let list = String::from_utf8_lossy(&list.stdout);
+    assert!(list.lines().any(|l| l == "com/maulingmonkey/jerk/example_hello_world_jar/Platform.class"), "{}", list);
+    assert!(list.lines().any(|l| l == "META-INF/versions/9/com/maulingmonkey/jerk/example_hello_world_jar/Platform.class"), "{}", list);
+
+    let extracted = Path::new(env!("CARGO_TARGET_TMPDIR")).join("multi-release");
+    let _ = std::fs::remove_dir_all(&extracted);
+    std::fs::create_dir_all(&extracted).unwrap();
+    let status = std::process::Command::new(&jar_exe).arg("xf").arg(jar).arg("META-INF/MANIFEST.MF").current_dir(&extracted).status().unwrap();
+    assert!(status.success());
+    let manifest = std::fs::read_to_string(extracted.join("META-INF/MANIFEST.MF")).unwrap();
+    assert!(manifest.contains("\r\nMulti-Release: true\r\n"), "{}", manifest);
+
+    // The test VM runs the crate's .jar, so the Java 9 version is picked (javac 9+ is required for --release anyways)
+    let package = "com.maulingmonkey.jerk.example_hello_world_jar";
+    assert_eq!(jerk::run_test!(package, "Platform", "release" => String), "9");
+    assert_eq!(jerk::run_test!(package, "Platform", "pid" => i64), std::process::id() as i64);
+}
+
+#[test] fn run_main() {
+    let hello = jerk::java::Run {
+        java_home:          Some(Path::new(env!("JAVA_HOME"))),
+        classpaths:         &[Path::new(env!("JERK_BUILD_JAR"))],
+        main_class:         Some("com.maulingmonkey.jerk.example_hello_world_jar.Hello"),
+        args:               &["jerk"],
+        system_properties:  &[("hello.exit", "3")],
+        ..jerk::java::Run::default()
+    }.output().unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&hello.stdout).trim(), "Hello, jerk!");
+    assert_eq!(hello.status.code(), Some(3));
+}
+
+#[test] fn javadoc() {
+    let docs = Path::new(env!("JERK_BUILD_JAVADOC"));
+    assert!(docs.join("index.html").exists());
+    assert!(docs.join("com/maulingmonkey/jerk/example_hello_world_jar/Hello.html").exists());
+    assert!(Path::new(env!("JERK_BUILD_JAVADOC_JAR")).exists());
+}
+
+#[test] fn runtime_image() {
+    let java_home = Path::new(env!("JAVA_HOME"));
+    if jerk::paths::java_feature_version(&java_home).unwrap() < 11 { return; } // no jlink before JDK 9, nor jdeps --print-module-deps before 11
+
+    let jar = Path::new(env!("JERK_BUILD_JAR"));
+    let runtime = Path::new(env!("CARGO_TARGET_TMPDIR")).join("runtime-image");
+    jerk::jlink::minimal_runtime(Some(java_home), &[jar], &runtime).unwrap();
+    assert!(jerk::paths::libjvm_dir(&runtime).is_ok());
+
+    let hello = jerk::java::Run {
+        java_home:          Some(&runtime),
+        classpaths:         &[Path::new(env!("JERK_BUILD_JAR"))],
+        main_class:         Some("com.maulingmonkey.jerk.example_hello_world_jar.Hello"),
+        ..jerk::java::Run::default()
+    }.output().unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&hello.stdout).trim(), "Hello, world!");
+    assert!(hello.status.success());
+}
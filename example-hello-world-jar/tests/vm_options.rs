@@ -0,0 +1,20 @@
+// Separate test binary, since options only apply to the first Java VM created in a process
+
+#[test] fn vm_options() {
+    std::env::set_var("JERK_TEST_JVM_OPTS", "-Djerk.example.env=from-env  -Xcheck:jni");
+    let options = jerk::test::TestVmOptions {
+        disable_assertions: vec!["com.maulingmonkey.jerk.example_hello_world_jar.Args".into()],
+        system_properties:  vec![("jerk.example.configured".into(), "yes".into())],
+        options:            vec!["-Djava.class.path=jerk-example-extra".into()], // appended to, rather than replacing, the crate's .jar
+        ..Default::default()
+    };
+    jerk::test::configure(options.clone()).unwrap();
+
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Args", "property", "jerk.example.configured" => String), "yes");
+    assert_eq!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Args", "property", "jerk.example.env" => String), "from-env");
+    assert!(jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Args", "property", "java.class.path" => String).ends_with("jerk-example-extra"));
+    jerk::run_test!("com.maulingmonkey.jerk.example_hello_world_jar", "Args", "check", 4, 1, 2); // would fail with assertions enabled
+
+    jerk::test::configure(options).unwrap();
+    assert!(jerk::test::configure(jerk::test::TestVmOptions::default()).is_err());
+}
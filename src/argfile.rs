@@ -0,0 +1,164 @@
+//! `@argfile`s for JDK tools, to avoid command line length limits
+//!
+//! Both `javac` and `jar` accept `@path` arguments, which they expand into whitespace separated, optionally quoted arguments read from `path`.
+//! Windows limits command lines to 32K UTF-16 code units, and `ARG_MAX` can be surprisingly small in containers, so large argument lists go into a file instead.
+//!
+//! The JDK reads argfiles with the platform's default charset, which may be anything from ASCII (`C` locale) to an ANSI codepage,
+//! so argfiles are written in that encoding.  If an argument can't be represented in it (or contains control characters), everything
+//! is passed on the command line instead.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Command lines longer than this (in bytes, roughly) will use an argfile.
+const MAX_COMMAND_LINE : usize = 8 * 1024;
+
+/// A temporary file (`@argfile`, generated manifest, etc.), deleted when dropped.
+pub(crate) struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(extension: &str, contents: impl AsRef<[u8]>) -> io::Result<Self> {
+        static NEXT : AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!("jerk-{}-{}.{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed), extension));
+        fs::write(&path, contents)?;
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &Path { &self.0 }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Append `args` to `cmd`, writing them into an `@argfile` instead if they're too long for the command line.
+///
+/// The returned [TempFile] (if any) must be kept alive until `cmd` has finished executing.
+pub(crate) fn args(cmd: &mut Command, args: Vec<OsString>) -> io::Result<Option<TempFile>> {
+    let len = args.iter().map(|a| a.len() + 3).sum::<usize>();
+    let contents = if len <= MAX_COMMAND_LINE { None } else { contents(&args).and_then(|contents| encode(&contents)) };
+    let contents = if let Some(c) = contents { c } else {
+        cmd.args(args);
+        return Ok(None);
+    };
+
+    let argfile = TempFile::new("args", &contents)?;
+    let mut at_path = OsString::from("@");
+    at_path.push(argfile.path());
+    cmd.arg(at_path);
+    Ok(Some(argfile))
+}
+
+/// The `@argfile` contents for `args`, one quoted argument per line.
+fn contents(args: &[OsString]) -> Option<String> {
+    let mut contents = String::new();
+    for arg in args.iter() {
+        contents.push_str(&quote(arg.to_str()?)?);
+        contents.push('\n');
+    }
+    Some(contents)
+}
+
+/// Quote `arg` for use in an `@argfile`, or return `None` if it can't be safely represented in one.
+///
+/// ```text
+/// C:\Program Files\Foo "Bar".java  =>  "C:\\Program Files\\Foo \"Bar\".java"
+/// ```
+pub(crate) fn quote(arg: &str) -> Option<String> {
+    // Backslash escapes for newlines etc. aren't understood by every JDK's argfile parser
+    if arg.chars().any(char::is_control) { return None; }
+
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for ch in arg.chars() {
+        if ch == '\\' || ch == '"' { quoted.push('\\'); }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    Some(quoted)
+}
+
+/// Encode `text` in the charset the JDK tools will read it with, or return `None` if it isn't representable in that charset.
+#[cfg(windows)]
+fn encode(text: &str) -> Option<Vec<u8>> {
+    use std::os::raw::c_int;
+    use std::ptr::{null, null_mut};
+
+    #[link(name = "kernel32")] extern "system" {
+        fn GetACP() -> u32;
+        fn WideCharToMultiByte(code_page: u32, flags: u32, wide: *const u16, wide_len: c_int, multi_byte: *mut u8, multi_byte_len: c_int, default_char: *const u8, used_default_char: *mut c_int) -> c_int;
+    }
+    const CP_UTF8 : u32 = 65001;
+    const WC_NO_BEST_FIT_CHARS : u32 = 0x400;
+
+    // The ANSI code page, which the JDK's default charset (and file names passed through `char*`) is derived from
+    let code_page = unsafe { GetACP() };
+    if code_page == CP_UTF8 || text.is_ascii() { return Some(text.as_bytes().to_vec()); }
+
+    let wide = text.encode_utf16().collect::<Vec<u16>>();
+    let mut used_default_char = 0;
+    let len = unsafe { WideCharToMultiByte(code_page, WC_NO_BEST_FIT_CHARS, wide.as_ptr(), wide.len() as c_int, null_mut(), 0, null(), &mut used_default_char) };
+    if len <= 0 || used_default_char != 0 { return None; }
+    let mut encoded = vec![0u8; len as usize];
+    let len = unsafe { WideCharToMultiByte(code_page, WC_NO_BEST_FIT_CHARS, wide.as_ptr(), wide.len() as c_int, encoded.as_mut_ptr(), len, null(), &mut used_default_char) };
+    if len <= 0 || used_default_char != 0 { return None; }
+    encoded.truncate(len as usize);
+    Some(encoded)
+}
+
+/// Encode `text` in the charset the JDK tools will read it with, or return `None` if it isn't representable in that charset.
+#[cfg(not(windows))]
+fn encode(text: &str) -> Option<Vec<u8>> {
+    if text.is_ascii() || locale_is_utf8() { Some(text.as_bytes().to_vec()) } else { None }
+}
+
+/// The JDK's default charset is always UTF-8 on macOS, and otherwise follows the locale's codeset.
+/// Other non-ASCII codesets (ISO-8859-1, EUC-JP, ...) aren't supported here, so fall back to the command line.
+#[cfg(not(windows))]
+fn locale_is_utf8() -> bool {
+    if cfg!(target_os = "macos") { return true; }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().filter_map(|var| std::env::var(var).ok()).find(|value| !value.is_empty()).unwrap_or_default();
+    let codeset = locale.split('.').nth(1).unwrap_or_default().split('@').next().unwrap_or_default().to_ascii_lowercase();
+    codeset == "utf-8" || codeset == "utf8"
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn quote_escapes() {
+        assert_eq!(quote("Foo.java").as_deref(), Some(r#""Foo.java""#));
+        assert_eq!(quote("").as_deref(), Some(r#""""#));
+        assert_eq!(quote(r#"C:\Program Files\Foo "Bar".java"#).as_deref(), Some(r#""C:\\Program Files\\Foo \"Bar\".java""#));
+        assert_eq!(quote("caf\u{E9}/\u{1F980}.java").as_deref(), Some("\"caf\u{E9}/\u{1F980}.java\""));
+    }
+
+    #[test] fn quote_rejects_control_characters() {
+        assert_eq!(quote("a\nb"), None);
+        assert_eq!(quote("a\tb"), None);
+        assert_eq!(quote("a\0b"), None);
+    }
+
+    #[test] fn contents_one_arg_per_line() {
+        let args = ["-d", "out dir", "A.java"].iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(contents(&args).as_deref(), Some("\"-d\"\n\"out dir\"\n\"A.java\"\n"));
+        assert_eq!(encode("\"-d\"\n").as_deref(), Some(&b"\"-d\"\n"[..]));
+    }
+
+    #[test] fn long_command_lines_use_argfiles() {
+        let args = (0 .. 1000).map(|i| OsString::from(format!("src/com/example/Class{}.java", i))).collect::<Vec<_>>();
+        let mut cmd = Command::new("javac");
+        let argfile = self::args(&mut cmd, args).unwrap().unwrap();
+        let contents = fs::read_to_string(argfile.path()).unwrap();
+        assert!(contents.starts_with("\"src/com/example/Class0.java\"\n"));
+        assert_eq!(contents.lines().count(), 1000);
+        let mut at_path = OsString::from("@");
+        at_path.push(argfile.path());
+        assert_eq!(format!("{:?}", cmd), format!("{:?} {:?}", "javac", at_path)); // Command::get_args requires Rust 1.57
+    }
+}
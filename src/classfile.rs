@@ -0,0 +1,311 @@
+//! Just enough of a `.class` file parser to find methods and their annotations
+//!
+//! https://docs.oracle.com/javase/specs/jvms/se17/html/jvms-4.html
+
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+pub(crate) const ACC_STATIC     : u16 = 0x0008;
+pub(crate) const ACC_BRIDGE     : u16 = 0x0040;
+pub(crate) const ACC_SYNTHETIC  : u16 = 0x1000;
+
+pub(crate) struct ClassFile {
+    /// Internal name, e.g. `"com/example/Outer$Inner"`
+    pub name:       String,
+    pub methods:    Vec<Method>,
+}
+
+pub(crate) struct Method {
+    pub access_flags:   u16,
+    pub name:           String,
+    /// e.g. `"()V"`
+    pub descriptor:     String,
+    /// Type descriptors of the method's (runtime visible and invisible) annotations, e.g. `"Lcom/example/Test;"`
+    pub annotations:    Vec<String>,
+}
+
+impl ClassFile {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Self::parse(&std::fs::read(path)?).map_err(|err| Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut r = Reader(data);
+        if r.u4()? != 0xCAFEBABE { return Err(invalid("not a .class file")); }
+        let _minor = r.u2()?;
+        let _major = r.u2()?;
+
+        // Constant pool: only UTF-8 strings and class references matter here
+        let count = r.u2()? as usize;
+        let mut utf8  = vec![None; count];
+        let mut class = vec![0u16; count];
+        let mut i = 1;
+        while i < count {
+            match r.u1()? {
+                1               => { let len = r.u2()? as usize; utf8[i] = Some(String::from_utf8_lossy(r.bytes(len)?).into_owned()); }, // modified UTF-8, close enough for names
+                7               => { class[i] = r.u2()?; },
+                3 | 4           => { r.bytes(4)?; },
+                5 | 6           => { r.bytes(8)?; i += 1; }, // longs and doubles take two slots
+                8 | 16 | 19 | 20=> { r.bytes(2)?; },
+                9 | 10 | 11 | 12 | 17 | 18 => { r.bytes(4)?; },
+                15              => { r.bytes(3)?; },
+                tag             => return Err(invalid(&format!("unknown constant pool tag {}", tag))),
+            }
+            i += 1;
+        }
+        let utf8_at = |index: u16| utf8.get(index as usize).and_then(|s| s.clone()).ok_or_else(|| invalid("bad constant pool index"));
+
+        let _access_flags = r.u2()?;
+        let this_class = r.u2()?;
+        let name = utf8_at(*class.get(this_class as usize).ok_or_else(|| invalid("bad this_class"))?)?;
+        let _super_class = r.u2()?;
+        let interfaces = r.u2()? as usize;
+        r.bytes(2 * interfaces)?;
+
+        let fields = r.u2()?;
+        for _ in 0 .. fields {
+            r.bytes(6)?;
+            skip_attributes(&mut r)?;
+        }
+
+        let mut methods = Vec::new();
+        for _ in 0 .. r.u2()? {
+            let access_flags = r.u2()?;
+            let name = utf8_at(r.u2()?)?;
+            let descriptor = utf8_at(r.u2()?)?;
+            let mut annotations = Vec::new();
+            for _ in 0 .. r.u2()? {
+                let attribute = utf8_at(r.u2()?)?;
+                let len = r.u4()? as usize;
+                let mut a = Reader(r.bytes(len)?);
+                if attribute == "RuntimeVisibleAnnotations" || attribute == "RuntimeInvisibleAnnotations" {
+                    for _ in 0 .. a.u2()? {
+                        annotations.push(utf8_at(a.u2()?)?);
+                        for _ in 0 .. a.u2()? {
+                            a.u2()?; // element_name_index
+                            skip_element_value(&mut a)?;
+                        }
+                    }
+                }
+            }
+            methods.push(Method { access_flags, name, descriptor, annotations });
+        }
+
+        Ok(Self { name, methods })
+    }
+}
+
+fn skip_attributes(r: &mut Reader) -> io::Result<()> {
+    for _ in 0 .. r.u2()? {
+        r.u2()?;
+        let len = r.u4()? as usize;
+        r.bytes(len)?;
+    }
+    Ok(())
+}
+
+fn skip_element_value(r: &mut Reader) -> io::Result<()> {
+    match r.u1()? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' | b'c' => { r.u2()?; },
+        b'e' => { r.u2()?; r.u2()?; },
+        b'@' => {
+            r.u2()?;
+            for _ in 0 .. r.u2()? { r.u2()?; skip_element_value(r)?; }
+        },
+        b'[' => {
+            for _ in 0 .. r.u2()? { skip_element_value(r)?; }
+        },
+        tag => return Err(invalid(&format!("unknown annotation element tag {:?}", tag as char))),
+    }
+    Ok(())
+}
+
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n { return Err(invalid("unexpected end of file")); }
+        let (bytes, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn u1(&mut self) -> io::Result<u8>  { Ok(self.bytes(1)?[0]) }
+    fn u2(&mut self) -> io::Result<u16> { let b = self.bytes(2)?; Ok(u16::from_be_bytes([b[0], b[1]])) }
+    fn u4(&mut self) -> io::Result<u32> { let b = self.bytes(4)?; Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])) }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn u2(value: u16) -> Vec<u8> { value.to_be_bytes().to_vec() }
+    fn u4(value: u32) -> Vec<u8> { value.to_be_bytes().to_vec() }
+
+    /// A hand-assembled constant pool
+    #[derive(Default)]
+    struct Pool { bytes: Vec<u8>, count: u16 }
+    impl Pool {
+        fn entry(&mut self, tag: u8, info: &[u8], slots: u16) -> u16 {
+            let index = self.count + 1;
+            self.bytes.push(tag);
+            self.bytes.extend_from_slice(info);
+            self.count += slots;
+            index
+        }
+        fn utf8(&mut self, value: &str) -> u16 { self.entry(1, &[u2(value.len() as u16), value.as_bytes().to_vec()].concat(), 1) }
+        fn class(&mut self, name: &str) -> u16 { let name = self.utf8(name); self.entry(7, &u2(name), 1) }
+    }
+
+    fn attribute(pool: &mut Pool, name: &str, info: &[u8]) -> Vec<u8> {
+        [u2(pool.utf8(name)), u4(info.len() as u32), info.to_vec()].concat()
+    }
+
+    /// A `field_info` or `method_info`
+    fn member(pool: &mut Pool, access_flags: u16, name: &str, descriptor: &str, attributes: &[Vec<u8>]) -> Vec<u8> {
+        [u2(access_flags), u2(pool.utf8(name)), u2(pool.utf8(descriptor)), u2(attributes.len() as u16), attributes.concat()].concat()
+    }
+
+    fn class_file(pool: &Pool, this_class: u16, fields: &[Vec<u8>], methods: &[Vec<u8>]) -> Vec<u8> {
+        [
+            u4(0xCAFEBABE), u2(0), u2(61),
+            u2(pool.count + 1), pool.bytes.clone(),
+            u2(0x0021), u2(this_class), u2(0),
+            u2(1), u2(this_class), // interfaces
+            u2(fields.len() as u16), fields.concat(),
+            u2(methods.len() as u16), methods.concat(),
+            u2(0), // attributes
+        ].concat()
+    }
+
+    /// `com/example/Adder` with a `ConstantValue` field, a `Code` attribute, and a `@Test`-annotated method
+    fn adder() -> Vec<u8> {
+        let mut pool = Pool::default();
+        let this_class = pool.class("com/example/Adder");
+        let one = pool.entry(3, &u4(1), 1);
+        let constant_value = attribute(&mut pool, "ConstantValue", &u2(one));
+        let field = member(&mut pool, ACC_STATIC, "ONE", "I", &[constant_value]);
+        let code = attribute(&mut pool, "Code", &[u2(0), u2(0), u4(1), vec![0xB1], u2(0), u2(0)].concat()); // return
+        let test = pool.utf8("Lcom/example/Test;");
+        let annotations = attribute(&mut pool, "RuntimeVisibleAnnotations", &[u2(1), u2(test), u2(0)].concat());
+        let method = member(&mut pool, ACC_STATIC, "test_add", "()V", &[code, annotations]);
+        class_file(&pool, this_class, &[field], &[method])
+    }
+
+    #[test] fn parse() {
+        let class = ClassFile::parse(&adder()).unwrap();
+        assert_eq!(class.name, "com/example/Adder");
+        assert_eq!(class.methods.len(), 1);
+        assert_eq!(class.methods[0].access_flags, ACC_STATIC);
+        assert_eq!(class.methods[0].name, "test_add");
+        assert_eq!(class.methods[0].descriptor, "()V");
+        assert_eq!(class.methods[0].annotations, vec!["Lcom/example/Test;"]);
+    }
+
+    #[test] fn constant_pool_tags() {
+        let mut pool = Pool::default();
+        let string      = pool.utf8("value");
+        let object      = pool.class("java/lang/Object");
+        let descriptor  = pool.utf8("()V");
+        let nat         = pool.entry(12, &[u2(string), u2(descriptor)].concat(), 1);   // NameAndType
+        pool.entry(3,  &u4(42), 1);                                                     // Integer
+        pool.entry(4,  &u4(1.5f32.to_bits()), 1);                                       // Float
+        pool.entry(8,  &u2(string), 1);                                                 // String
+        pool.entry(9,  &[u2(object), u2(nat)].concat(), 1);                             // Fieldref
+        let method  = pool.entry(10, &[u2(object), u2(nat)].concat(), 1);               // Methodref
+        pool.entry(11, &[u2(object), u2(nat)].concat(), 1);                             // InterfaceMethodref
+        pool.entry(15, &[vec![6], u2(method)].concat(), 1);                             // MethodHandle (REF_invokeStatic)
+        pool.entry(16, &u2(descriptor), 1);                                             // MethodType
+        pool.entry(17, &[u2(0), u2(nat)].concat(), 1);                                  // Dynamic
+        pool.entry(18, &[u2(0), u2(nat)].concat(), 1);                                  // InvokeDynamic
+        pool.entry(19, &u2(string), 1);                                                 // Module
+        pool.entry(20, &u2(string), 1);                                                 // Package
+        let this_class = pool.class("com/example/Tags");
+        let method = member(&mut pool, 0, "run", "()V", &[]);
+        let class = ClassFile::parse(&class_file(&pool, this_class, &[], &[method])).unwrap();
+        assert_eq!(class.name, "com/example/Tags");
+        assert_eq!(class.methods[0].name, "run");
+
+        let mut pool = Pool::default();
+        pool.entry(2, &[], 1); // unused since Java 1.0
+        let err = ClassFile::parse(&class_file(&pool, 1, &[], &[])).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "unknown constant pool tag 2");
+    }
+
+    #[test] fn constant_pool_long_and_double() {
+        let mut pool = Pool::default();
+        let long    = pool.entry(5, &42u64.to_be_bytes(), 2);
+        let double  = pool.entry(6, &1.5f64.to_bits().to_be_bytes(), 2);
+        assert_eq!((long, double), (1, 3));
+        let this_class = pool.class("com/example/Wide"); // index 6 (name at 5), not 4 (name at 3)
+        let method = member(&mut pool, 0, "run", "()V", &[]);
+        let class = ClassFile::parse(&class_file(&pool, this_class, &[], &[method])).unwrap();
+        assert_eq!(class.name, "com/example/Wide");
+        assert_eq!(class.methods[0].name, "run");
+
+        // The unusable second slot of a long isn't a string
+        let mut bad = member(&mut pool, 0, "run", "()V", &[]);
+        bad[2..4].copy_from_slice(&u2(long + 1));
+        let err = ClassFile::parse(&class_file(&pool, this_class, &[], &[bad])).err().unwrap();
+        assert_eq!(err.to_string(), "bad constant pool index");
+    }
+
+    #[test] fn nested_annotation_element_values() {
+        let mut pool = Pool::default();
+        let this_class  = pool.class("com/example/Nested");
+        let test        = pool.utf8("Lcom/example/Test;");
+        let other       = pool.utf8("Lcom/example/Other;");
+        let inner       = pool.utf8("Lcom/example/Inner;");
+        let kind        = pool.utf8("Lcom/example/Kind;");
+        let fast        = pool.utf8("FAST");
+        let value       = pool.utf8("value");
+        let long        = pool.entry(5, &7u64.to_be_bytes(), 2);
+
+        // @Test(value = @Inner(value = { Kind.FAST, "value", Object.class }, value = 7L), value = true) - names needn't be unique here
+        let element = [
+            vec![b'@'], u2(inner), u2(2),
+                u2(value), vec![b'['], u2(3), vec![b'e'], u2(kind), u2(fast), vec![b's'], u2(value), vec![b'c'], u2(kind),
+                u2(value), vec![b'J'], u2(long),
+        ].concat();
+        let visible = [u2(1), u2(test), u2(2), u2(value), element, u2(value), vec![b'Z'], u2(0)].concat();
+        let visible = attribute(&mut pool, "RuntimeVisibleAnnotations", &visible);
+        let invisible = attribute(&mut pool, "RuntimeInvisibleAnnotations", &[u2(1), u2(other), u2(0)].concat());
+        let annotated = member(&mut pool, ACC_STATIC, "annotated", "()V", &[visible, invisible]);
+        let after = member(&mut pool, ACC_STATIC, "after", "()V", &[]);
+
+        let class = ClassFile::parse(&class_file(&pool, this_class, &[], &[annotated, after])).unwrap();
+        assert_eq!(class.methods[0].annotations, vec!["Lcom/example/Test;", "Lcom/example/Other;"]);
+        assert_eq!(class.methods[1].name, "after");
+        assert!(class.methods[1].annotations.is_empty());
+
+        let unknown = attribute(&mut pool, "RuntimeVisibleAnnotations", &[u2(1), u2(test), u2(1), u2(value), vec![b'x'], u2(0)].concat());
+        let method = member(&mut pool, ACC_STATIC, "unknown", "()V", &[unknown]);
+        let err = ClassFile::parse(&class_file(&pool, this_class, &[], &[method])).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "unknown annotation element tag 'x'");
+    }
+
+    #[test] fn truncated() {
+        let class = adder();
+        // The class's own attributes (the last 2 bytes here) aren't read
+        for len in 0 .. class.len() - 2 {
+            let err = ClassFile::parse(&class[..len]).err().unwrap_or_else(|| panic!("parsed {} of {} bytes", len, class.len()));
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{} of {} bytes", len, class.len());
+        }
+
+        let mut not_class = class.clone();
+        not_class[0] = 0;
+        assert_eq!(ClassFile::parse(&not_class).err().unwrap().to_string(), "not a .class file");
+
+        // Annotations past the end of their attribute
+        let mut pool = Pool::default();
+        let this_class = pool.class("com/example/Short");
+        let test = pool.utf8("Lcom/example/Test;");
+        let annotations = attribute(&mut pool, "RuntimeVisibleAnnotations", &[u2(2), u2(test), u2(0)].concat());
+        let method = member(&mut pool, 0, "run", "()V", &[annotations]);
+        let err = ClassFile::parse(&class_file(&pool, this_class, &[], &[method])).err().unwrap();
+        assert_eq!(err.to_string(), "unexpected end of file");
+    }
+}
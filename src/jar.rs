@@ -2,19 +2,42 @@
 //!
 //! | Command       | Description                   | API |
 //! | ------------- | ----------------------------- | --- |
-//! | `jar c...`    | Create `.jar`                 | `jar::Archive{ ... }.create()`
+//! | `jar c...`    | Create `.jar`                 | `jar::Archive{ ... }.create()` or `jar::Archive{ ... }.write()` (pure Rust, reproducible)
 //! | `jar u...`    | Update `.jar`                 | `jar::Archive{ ... }.update()`
-//! | `jar x...`    | Extract `.jar`                | *NYI*
-//! | `jar t...`    | List `.jar` table of contents | *NYI*
-//! | `jar i...`    | Generate `.jar` index         | *NYI*
+//! | `jar x...`    | Extract `.jar`                | `jar::Archive{ ... }.extract(dest)`
+//! | `jar t...`    | List `.jar` table of contents | `jar::Archive{ ... }.list()`
+//! | `jar i...`    | Generate `.jar` index         | `jar::Archive{ ... }.index()`
 
-use std::io::{Error, ErrorKind};
-use std::path::{Path};
+use crate::{argfile, tool};
+use crate::zip::{self, DosDateTime, Method};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
 /// std::io::[Result](https://doc.rust-lang.org/std/io/type.Result.html)
 pub type Result<T> = std::io::Result<T>;
 
+/// An entry in a `.jar`, as returned by [Archive::list](struct.Archive.html#method.list)
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entry {
+    pub name:               String,
+    pub size:               u64,
+    pub compressed_size:    u64,
+    /// Seconds since the Unix epoch.  Taken from the entry's extended timestamp extra field (`0x5455`, UTC) if it has one.
+    /// Otherwise from its timezone-less MS-DOS timestamp, interpreted as UTC:  right for `.jar`s built by jerk, but `bin/jar` and most
+    /// other zip tools write local time, so those will be off by the UTC offset of whoever built the `.jar`.
+    pub modified:           u64,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool { self.name.ends_with('/') }
+}
+
 /// Create or update a `.jar` file
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Archive<'a> {
@@ -22,6 +45,8 @@ pub struct Archive<'a> {
 
     pub jar_file:       Option<&'a Path>,
     pub manifest_file:  Option<&'a Path>,
+    /// A manifest to use instead of [manifest_file](#structfield.manifest_file)
+    pub manifest:       Option<&'a Manifest>,
     pub entry_point:    Option<String>,
 
     pub verbose:                        bool,
@@ -30,6 +55,10 @@ pub struct Archive<'a> {
     pub preserve_original_filenames:    bool,
     pub no_manifest:                    bool,
 
+    /// Seconds since the Unix epoch to timestamp every entry with when using [write](#method.write).
+    /// Defaults to `$SOURCE_DATE_EPOCH` if set, or 1980-01-01 otherwise.
+    pub timestamp:                      Option<u64>,
+
     pub files: &'a [(&'a Path, &'a [&'a Path])],
 
     #[doc(hidden)] pub _non_exhaustive: (),
@@ -42,6 +71,7 @@ impl<'a> std::default::Default for Archive<'a> {
 
             jar_file:           None,
             manifest_file:      None,
+            manifest:           None,
             entry_point:        None,
 
             verbose:                        false,
@@ -50,6 +80,8 @@ impl<'a> std::default::Default for Archive<'a> {
             preserve_original_filenames:    false,
             no_manifest:                    false,
 
+            timestamp:                      None,
+
             files: &[][..],
 
             _non_exhaustive:    (),
@@ -76,23 +108,192 @@ impl<'a> Archive<'a> {
         self.exec('u')
     }
 
+    /// Create a new archive without `bin/jar`, producing byte-for-byte reproducible output.
+    ///
+    /// * `META-INF/MANIFEST.MF` is written first, followed by all other entries sorted by name.
+    /// * Every entry shares the same [timestamp](#structfield.timestamp), and no permissions or other filesystem metadata are stored.
+    /// * Entries are deflated, unless [uncompressed](#structfield.uncompressed) is set.
+    /// * [pack200_normalization](#structfield.pack200_normalization) and [preserve_original_filenames](#structfield.preserve_original_filenames) are not supported.
+    pub fn write(&self) -> Result<()> {
+        let jar_file = self.jar_file()?;
+        if self.pack200_normalization       { return Err(Error::new(ErrorKind::InvalidInput, "pack200_normalization is not supported by jar::Archive::write")); }
+        if self.preserve_original_filenames { return Err(Error::new(ErrorKind::InvalidInput, "preserve_original_filenames is not supported by jar::Archive::write")); }
+
+        let modified = self.modified()?;
+        let method = if self.uncompressed { Method::Stored } else { Method::Deflated };
+
+        let mut entries = BTreeMap::new();
+        for (dir, files) in self.files {
+            for file in *files {
+                collect_entries(&dir.join(file), &entry_name(file)?, &mut entries)?;
+            }
+        }
+
+        let mut zip = zip::Writer::new(BufWriter::new(File::create(jar_file)?));
+        if !self.no_manifest {
+            let mut manifest = self.effective_manifest()?;
+            if manifest.attribute("Created-By").is_none() { manifest.attributes.push(("Created-By".into(), "jerk".into())); }
+            let manifest = manifest.serialize()?;
+            entries.remove("META-INF/");
+            entries.remove("META-INF/MANIFEST.MF");
+            zip.directory("META-INF/", modified, &JAR_MAGIC)?;
+            zip.file("META-INF/MANIFEST.MF", method, manifest.as_bytes(), modified)?;
+        }
+
+        for (name, path) in entries.iter() {
+            if self.verbose { println!("adding: {}", name); }
+            if name.ends_with('/') {
+                zip.directory(name, modified, &[])?;
+            } else {
+                zip.file(name, method, &fs::read(path)?, modified)?;
+            }
+        }
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// List the contents of [jar_file](#structfield.jar_file) without `bin/jar`.
+    pub fn list(&self) -> Result<Vec<Entry>> {
+        let jar = zip::Reader::open(self.jar_file()?)?;
+        Ok(jar.entries().iter().map(|e| Entry {
+            name:               e.name.clone(),
+            size:               e.uncompressed.into(),
+            compressed_size:    e.compressed.into(),
+            modified:           e.modified_unix(),
+            _non_exhaustive:    (),
+        }).collect())
+    }
+
+    /// Extract the contents of [jar_file](#structfield.jar_file) into `dest` without `bin/jar`.
+    ///
+    /// Entries that would escape `dest` (absolute paths, `..` components, etc.) are rejected.
+    pub fn extract(&self, dest: &Path) -> Result<()> {
+        let jar = zip::Reader::open(self.jar_file()?)?;
+        for entry in jar.entries() {
+            let escapes = || Error::new(ErrorKind::InvalidData, format!("refusing to extract jar entry outside of destination: {}", entry.name));
+            if entry.name.starts_with('/') { return Err(escapes()); }
+            let mut path = dest.to_owned();
+            for component in entry.name.split('/').filter(|c| !c.is_empty()) {
+                let mut components = Path::new(component).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(c)), None) => path.push(c),
+                    _other => return Err(escapes()),
+                }
+            }
+
+            if self.verbose { println!("{}: {}", if entry.is_dir() { "  created" } else { "inflated" }, entry.name); }
+            if entry.is_dir() {
+                fs::create_dir_all(&path)?;
+            } else {
+                if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
+                fs::write(&path, jar.data(entry)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate `META-INF/INDEX.LIST` for [jar_file](#structfield.jar_file) without `bin/jar`.
+    ///
+    /// The index covers `jar_file` and every `.jar` it (transitively) references via `Class-Path`, resolved relative to `jar_file`.
+    pub fn index(&self) -> Result<()> {
+        let jar_file = self.jar_file()?;
+        let root = jar_file.parent().unwrap_or_else(|| Path::new(""));
+        let jar = zip::Reader::open(jar_file)?;
+
+        let mut index = String::from("JarIndex-Version: 1.0\n\n");
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(entry_name(Path::new(jar_file.file_name().unwrap_or_default()))?);
+        while let Some(name) = queue.pop_front() {
+            if !seen.insert(name.clone()) { continue; }
+            let dep;
+            let reader = if seen.len() == 1 { &jar } else { dep = zip::Reader::open(&root.join(&name))?; &dep };
+
+            let packages = reader.entries().iter()
+                .filter(|e| !e.is_dir() && !e.name.starts_with("META-INF/"))
+                .map(|e| e.name.rsplit_once('/').map_or(e.name.as_str(), |(dir, _file)| dir))
+                .collect::<BTreeSet<_>>();
+            index.push_str(&name);
+            index.push('\n');
+            for package in packages {
+                index.push_str(package);
+                index.push('\n');
+            }
+            index.push('\n');
+
+            if let Some(manifest) = reader.find("META-INF/MANIFEST.MF") {
+                let manifest = Manifest::parse(&String::from_utf8_lossy(&reader.data(manifest)?))?;
+                let dir = name.rsplit_once('/').map_or("", |(dir, _file)| dir);
+                for class_path in manifest.class_path.iter() {
+                    queue.push_back(if dir.is_empty() { class_path.to_owned() } else { format!("{}/{}", dir, class_path) });
+                }
+            }
+        }
+
+        let method = if self.uncompressed { Method::Stored } else { Method::Deflated };
+        let tmp = jar_file.with_extension("jar.tmp");
+        let mut zip = zip::Writer::new(BufWriter::new(File::create(&tmp)?));
+        let mut indexed = false;
+        for entry in jar.entries().iter().filter(|e| e.name != "META-INF/INDEX.LIST") {
+            if !indexed && entry.name != "META-INF/" && entry.name != "META-INF/MANIFEST.MF" {
+                zip.file("META-INF/INDEX.LIST", method, index.as_bytes(), self.modified()?)?;
+                indexed = true;
+            }
+            zip.copy(&jar, entry)?;
+        }
+        if !indexed { zip.file("META-INF/INDEX.LIST", method, index.as_bytes(), self.modified()?)?; }
+        zip.finish()?;
+        fs::rename(&tmp, jar_file)
+    }
+
+    fn jar_file(&self) -> Result<&'a Path> {
+        self.jar_file.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "jar_file must be set"))
+    }
+
+    fn modified(&self) -> Result<DosDateTime> {
+        Ok(DosDateTime::from_unix(match self.timestamp {
+            Some(timestamp) => timestamp,
+            None            => source_date_epoch()?.unwrap_or(0),
+        }))
+    }
+
+    /// [manifest](#structfield.manifest), [manifest_file](#structfield.manifest_file), or an empty manifest, plus [entry_point](#structfield.entry_point)
+    fn effective_manifest(&self) -> Result<Manifest> {
+        let mut manifest = match (self.manifest, self.manifest_file) {
+            (Some(_), Some(_))      => return Err(Error::new(ErrorKind::InvalidInput, "manifest and manifest_file are mutually exclusive")),
+            (Some(manifest), None)  => manifest.clone(),
+            (None, Some(path))      => Manifest::read(path)?,
+            (None, None)            => Manifest::default(),
+        };
+        if let Some(entry_point) = self.entry_point.as_ref() {
+            match manifest.main_class.as_ref() {
+                Some(main_class) if main_class != entry_point => return Err(Error::new(ErrorKind::InvalidInput, format!("entry_point {:?} conflicts with manifest Main-Class {:?}", entry_point, main_class))),
+                _ => manifest.main_class = Some(entry_point.clone()),
+            }
+        }
+        Ok(manifest)
+    }
+
     fn exec(&self, create_or_update: char) -> Result<()> {
-        let mut java_home_buf = None;
-        let java_home = self.java_home.or_else(||{
-            java_home_buf = Some(crate::search::find_java_home()?);
-            java_home_buf.as_ref().map(|p| &**p)
-        }).ok_or_else(||
-            Error::new(ErrorKind::NotFound, "JAVA_HOME not set and could not be found, unable to run")
-        )?;
+        let java_home = tool::java_home(self.java_home)?;
+
+        // bin/jar only accepts manifest files, and rejects `e` if the manifest already has a Main-Class
+        let manifest_tmp = match self.manifest {
+            Some(_) => Some(argfile::TempFile::new("MF", &self.effective_manifest()?.serialize()?)?),
+            None    => None,
+        };
+        let manifest_file   = manifest_tmp.as_ref().map(|tmp| tmp.path()).or(self.manifest_file);
+        let entry_point     = if manifest_tmp.is_some() { None } else { self.entry_point.as_ref() };
 
         let mut flags_arg = String::new();
         for (flag, cond) in [
             (create_or_update, true),
             ('v', self.verbose),
             ('f', self.jar_file.is_some()),
-            ('m', self.manifest_file.is_some()),
+            ('m', manifest_file.is_some()),
             ('n', self.pack200_normalization),
-            ('e', self.entry_point.is_some()),
+            ('e', entry_point.is_some()),
             ('0', self.uncompressed),
             ('P', self.preserve_original_filenames),
             ('M', self.no_manifest),
@@ -102,20 +303,23 @@ impl<'a> Archive<'a> {
             }
         }
 
-        let mut cmd = Command::new(java_home.join("bin/jar"));
-        cmd.arg(&flags_arg);
-
-        if let Some(jar_file)       = self.jar_file.as_ref()        { cmd.arg(jar_file); }
-        if let Some(manifest_file)  = self.manifest_file.as_ref()   { cmd.arg(manifest_file); }
-        if let Some(entry_point)    = self.entry_point.as_ref()     { cmd.arg(entry_point); }
+        let mut args = Vec::<OsString>::new();
+        if let Some(jar_file)       = self.jar_file.as_ref()        { args.push(jar_file.into()); }
+        if let Some(manifest_file)  = manifest_file                 { args.push(manifest_file.into()); }
+        if let Some(entry_point)    = entry_point                   { args.push(entry_point.into()); }
 
         for (dir, files) in self.files {
-            cmd.arg("-C").arg(dir);
+            args.push("-C".into());
+            args.push(dir.into());
             for file in *files {
-                cmd.arg(file);
+                args.push(file.into());
             }
         }
 
+        let mut cmd = Command::new(java_home.join("bin/jar"));
+        cmd.arg(&flags_arg);
+        let _argfile = argfile::args(&mut cmd, args)?;
+
         let status = cmd.status()?;
         if status.success() {
             Ok(())
@@ -124,3 +328,399 @@ impl<'a> Archive<'a> {
         }
     }
 }
+
+/// `META-INF/MANIFEST.MF` contents.  See <https://docs.oracle.com/en/java/javase/17/docs/specs/jar/jar.html#jar-manifest>
+///
+/// Common main section attributes get typed fields.  Anything else goes in [attributes](#structfield.attributes) or [sections](#structfield.sections).
+/// `Manifest-Version: 1.0` is always written first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Manifest {
+    /// `Main-Class`
+    pub main_class:             Option<String>,
+    /// `Class-Path` - relative URLs of other `.jar`s, space separated when written
+    pub class_path:             Vec<String>,
+    /// `Implementation-Title`
+    pub implementation_title:   Option<String>,
+    /// `Implementation-Version`
+    pub implementation_version: Option<String>,
+    /// `Implementation-Vendor`
+    pub implementation_vendor:  Option<String>,
+    /// `Multi-Release: true`
+    pub multi_release:          bool,
+    /// `Add-Opens` - `module/package` pairs to open when launched via `java -jar`, space separated when written
+    pub add_opens:              Vec<String>,
+    /// `Add-Exports` - `module/package` pairs to export when launched via `java -jar`, space separated when written
+    pub add_exports:            Vec<String>,
+    /// `Automatic-Module-Name`
+    pub automatic_module_name:  Option<String>,
+    /// Any other main section attributes, in order
+    pub attributes:             Vec<(String, String)>,
+    /// Per-entry sections, in order
+    pub sections:               Vec<ManifestSection>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+/// A per-entry section of a [Manifest]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ManifestSection {
+    /// `Name` - the entry (or package directory) these attributes apply to
+    pub name:           String,
+    pub attributes:     Vec<(String, String)>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Read and [parse](#method.parse) a `MANIFEST.MF` file
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|err| Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+    }
+
+    /// Parse `MANIFEST.MF` contents, joining continuation lines.  `Manifest-Version` is discarded.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut manifest = Manifest::default();
+        let mut sections = vec![Vec::<(String, String)>::new()];
+        for (n, line) in text.split('\n').map(|l| l.trim_end_matches('\r')).enumerate() {
+            let section = sections.last_mut().unwrap();
+            if line.is_empty() {
+                if !section.is_empty() { sections.push(Vec::new()); }
+            } else if let Some(continuation) = line.strip_prefix(' ') {
+                let (_, value) = section.last_mut().ok_or_else(|| invalid_manifest(n, "continuation line without a preceding attribute"))?;
+                value.push_str(continuation);
+            } else {
+                let (name, value) = line.split_once(':').ok_or_else(|| invalid_manifest(n, "expected `Name: Value`"))?;
+                if !valid_attribute_name(name) { return Err(invalid_manifest(n, &format!("invalid attribute name {:?}", name))); }
+                section.push((name.to_owned(), value.strip_prefix(' ').unwrap_or(value).to_owned()));
+            }
+        }
+
+        let mut sections = sections.into_iter().filter(|s| !s.is_empty());
+        for (name, value) in sections.next().unwrap_or_default() {
+            let list = || value.split_whitespace().map(String::from).collect::<Vec<_>>();
+            match name.to_ascii_lowercase().as_str() {
+                "manifest-version"          => {},
+                "main-class"                => manifest.main_class              = Some(value),
+                "class-path"                => manifest.class_path              = list(),
+                "implementation-title"      => manifest.implementation_title    = Some(value),
+                "implementation-version"    => manifest.implementation_version  = Some(value),
+                "implementation-vendor"     => manifest.implementation_vendor   = Some(value),
+                "multi-release"             => manifest.multi_release           = value.eq_ignore_ascii_case("true"),
+                "add-opens"                 => manifest.add_opens               = list(),
+                "add-exports"               => manifest.add_exports             = list(),
+                "automatic-module-name"     => manifest.automatic_module_name   = Some(value),
+                _other                      => manifest.attributes.push((name, value)),
+            }
+        }
+
+        for mut attributes in sections {
+            let (name, value) = attributes.remove(0);
+            if !name.eq_ignore_ascii_case("Name") { return Err(Error::new(ErrorKind::InvalidData, format!("manifest section starts with {:?} instead of \"Name\"", name))); }
+            manifest.sections.push(ManifestSection { name: value, attributes, _non_exhaustive: () });
+        }
+
+        Ok(manifest)
+    }
+
+    /// Get an attribute from [attributes](#structfield.attributes) by case insensitive name
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// Serialize to `MANIFEST.MF` contents: `\r\n` line endings, lines wrapped at 72 bytes.
+    ///
+    /// Fails if any attribute has an invalid name, or a value containing a newline or nul, or if [attributes](#structfield.attributes)
+    /// contains `Manifest-Version` or an attribute with a typed field (e.g. `Main-Class`), matched case insensitively like [parse](#method.parse).
+    pub fn serialize(&self) -> Result<String> {
+        let mut out = String::new();
+        write_attribute(&mut out, "Manifest-Version", "1.0")?;
+        let typed = [
+            ("Main-Class",              self.main_class.clone()),
+            ("Class-Path",              Some(self.class_path.join(" ")).filter(|v| !v.is_empty())),
+            ("Implementation-Title",    self.implementation_title.clone()),
+            ("Implementation-Version",  self.implementation_version.clone()),
+            ("Implementation-Vendor",   self.implementation_vendor.clone()),
+            ("Multi-Release",           Some(String::from("true")).filter(|_| self.multi_release)),
+            ("Add-Opens",               Some(self.add_opens.join(" ")).filter(|v| !v.is_empty())),
+            ("Add-Exports",             Some(self.add_exports.join(" ")).filter(|v| !v.is_empty())),
+            ("Automatic-Module-Name",   self.automatic_module_name.clone()),
+        ];
+        for (name, value) in typed.iter() {
+            if let Some(value) = value { write_attribute(&mut out, name, value)?; }
+        }
+        for (name, value) in self.attributes.iter() {
+            if name.eq_ignore_ascii_case("Manifest-Version") || typed.iter().any(|(typed, _)| name.eq_ignore_ascii_case(typed)) {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("manifest attribute {} has a typed Manifest field, and can't also be in attributes", name)));
+            }
+            write_attribute(&mut out, name, value)?;
+        }
+        out.push_str("\r\n");
+
+        for section in self.sections.iter() {
+            write_attribute(&mut out, "Name", &section.name)?;
+            for (name, value) in section.attributes.iter() { write_attribute(&mut out, name, value)?; }
+            out.push_str("\r\n");
+        }
+        Ok(out)
+    }
+}
+
+/// `alphanum *headerchar` where `headerchar` is `alphanum | - | _`, at most 70 bytes
+fn valid_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.len() <= 70
+        && chars.next().map_or(false, |ch| ch.is_ascii_alphanumeric())
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+}
+
+/// Write `name: value\r\n`, wrapping at 72 bytes with continuation lines (which start with a single space.)
+fn write_attribute(out: &mut String, name: &str, value: &str) -> Result<()> {
+    if !valid_attribute_name(name) { return Err(Error::new(ErrorKind::InvalidInput, format!("invalid manifest attribute name {:?}", name))); }
+    if value.contains(&['\r', '\n', '\0'][..]) { return Err(Error::new(ErrorKind::InvalidInput, format!("manifest attribute {} contains a newline or nul", name))); }
+
+    let line = format!("{}: {}", name, value);
+    let mut rest = line.as_str();
+    let mut max = 72;
+    loop {
+        let mut split = rest.len().min(max);
+        while !rest.is_char_boundary(split) { split -= 1; }
+        out.push_str(&rest[..split]);
+        out.push_str("\r\n");
+        rest = &rest[split..];
+        if rest.is_empty() { return Ok(()); }
+        out.push(' ');
+        max = 71;
+    }
+}
+
+fn invalid_manifest(line: usize, message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("invalid manifest, line {}: {}", line + 1, message))
+}
+
+/// The "JAR magic" extra field `bin/jar` puts on the first entry, marking the file as an executable `.jar` on some platforms.
+const JAR_MAGIC : [u8; 4] = [0xFE, 0xCA, 0x00, 0x00];
+
+fn source_date_epoch() -> Result<Option<u64>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch)   => epoch.trim().parse().map(Some).map_err(|_| Error::new(ErrorKind::InvalidInput, format!("SOURCE_DATE_EPOCH is not a valid timestamp: {:?}", epoch))),
+        Err(_)      => Ok(None),
+    }
+}
+
+/// Convert a relative path like `./com/example/Foo.class` into a jar entry name like `com/example/Foo.class`.
+fn entry_name(path: &Path) -> Result<String> {
+    let mut name = String::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::Normal(c) => {
+                let c = c.to_str().ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("jar entry path is not valid Unicode: {}", path.display())))?;
+                if !name.is_empty() { name.push('/'); }
+                name.push_str(c);
+            },
+            _other => return Err(Error::new(ErrorKind::InvalidInput, format!("jar entry path must be relative and not contain `..`: {}", path.display()))),
+        }
+    }
+    Ok(name)
+}
+
+fn collect_entries(path: &Path, name: &str, entries: &mut BTreeMap<String, PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        if !name.is_empty() { entries.insert(format!("{}/", name), path.to_owned()); }
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let child = entry.file_name();
+            let child = child.to_str().ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("jar entry path is not valid Unicode: {}", entry.path().display())))?;
+            let child_name = if name.is_empty() { child.to_owned() } else { format!("{}/{}", name, child) };
+            collect_entries(&entry.path(), &child_name, entries)?;
+        }
+    } else if entries.insert(name.to_owned(), path.to_owned()).is_some() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("duplicate jar entry: {}", name)));
+    }
+    Ok(())
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    /// A fresh, empty directory under the system temp dir
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jerk-jar-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `classes/` with a couple of packages, written into `dir`
+    fn classes(dir: &Path) -> PathBuf {
+        let classes = dir.join("classes");
+        fs::create_dir_all(classes.join("com/example/inner")).unwrap();
+        fs::write(classes.join("com/example/B.class"), b"B").unwrap();
+        fs::write(classes.join("com/example/A.class"), b"A").unwrap();
+        fs::write(classes.join("com/example/inner/C.class"), b"C").unwrap();
+        classes
+    }
+
+    #[test] fn write_reproducible() {
+        let dir = temp_dir("write");
+        let classes = classes(&dir);
+        let manifest = Manifest { main_class: Some("com.example.A".into()), ..Manifest::default() };
+        let write = |name: &str, timestamp| {
+            let jar = dir.join(name);
+            Archive {
+                jar_file:   Some(&jar),
+                manifest:   Some(&manifest),
+                timestamp:  Some(timestamp),
+                files:      &[(&classes, &[Path::new(".")])],
+                ..Archive::default()
+            }.write().unwrap();
+            fs::read(&jar).unwrap()
+        };
+
+        let a = write("a.jar", 1_600_000_000);
+        assert_eq!(a, write("b.jar", 1_600_000_000));
+        assert_ne!(a, write("c.jar", 1_600_000_002));
+
+        let list = Archive { jar_file: Some(&dir.join("a.jar")), ..Archive::default() }.list().unwrap();
+        let names = list.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["META-INF/", "META-INF/MANIFEST.MF", "com/", "com/example/", "com/example/A.class", "com/example/B.class", "com/example/inner/", "com/example/inner/C.class"]);
+        assert!(list.iter().all(|e| e.modified == 1_600_000_000));
+
+        let jar = zip::Reader::new(a).unwrap();
+        let manifest = String::from_utf8(jar.data(jar.find("META-INF/MANIFEST.MF").unwrap()).unwrap()).unwrap();
+        assert_eq!(manifest, "Manifest-Version: 1.0\r\nMain-Class: com.example.A\r\nCreated-By: jerk\r\n\r\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test] fn extract() {
+        let dir = temp_dir("extract");
+        let classes = classes(&dir);
+        let jar = dir.join("classes.jar");
+        let archive = Archive { jar_file: Some(&jar), files: &[(&classes, &[Path::new("com")])], ..Archive::default() };
+        archive.write().unwrap();
+        archive.extract(&dir.join("out")).unwrap();
+        assert_eq!(fs::read(dir.join("out/com/example/A.class")).unwrap(), b"A");
+        assert_eq!(fs::read(dir.join("out/com/example/inner/C.class")).unwrap(), b"C");
+        assert!(dir.join("out/META-INF/MANIFEST.MF").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test] fn extract_rejects_escapes() {
+        let dir = temp_dir("extract-escapes");
+        let mut names = vec!["../escaped.txt", "a/../../escaped.txt", "/escaped.txt"];
+        if cfg!(windows) { names.extend_from_slice(&["C:/escaped.txt", "C:escaped.txt", "a\\..\\..\\escaped.txt"]); }
+        for (i, name) in names.iter().enumerate() {
+            let jar = dir.join(format!("evil{}.jar", i));
+            let mut zip = zip::Writer::new(File::create(&jar).unwrap());
+            zip.file(name, Method::Stored, b"escaped", DosDateTime::MIN).unwrap();
+            zip.finish().unwrap();
+
+            let out = dir.join("out").join(i.to_string());
+            let err = Archive { jar_file: Some(&jar), ..Archive::default() }.extract(&out).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", name);
+            assert!(err.to_string().contains("refusing to extract"), "{}: {}", name, err);
+        }
+        assert!(!dir.join("escaped.txt").exists());
+        assert!(!dir.join("out/escaped.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test] fn index() {
+        let dir = temp_dir("index");
+        let classes = classes(&dir);
+        let dep_classes = dir.join("dep-classes");
+        fs::create_dir_all(dep_classes.join("org/dep")).unwrap();
+        fs::write(dep_classes.join("org/dep/D.class"), b"D").unwrap();
+        fs::write(dep_classes.join("Root.class"), b"Root").unwrap();
+        fs::create_dir_all(dir.join("lib")).unwrap();
+
+        let dep = dir.join("lib/dep.jar");
+        Archive { jar_file: Some(&dep), files: &[(&dep_classes, &[Path::new(".")])], ..Archive::default() }.write().unwrap();
+
+        let main = dir.join("main.jar");
+        let manifest = Manifest { class_path: vec!["lib/dep.jar".into(), "main.jar".into()], ..Manifest::default() };
+        let archive = Archive { jar_file: Some(&main), manifest: Some(&manifest), files: &[(&classes, &[Path::new(".")])], ..Archive::default() };
+        archive.write().unwrap();
+        archive.index().unwrap();
+        archive.index().unwrap(); // replaces the existing index
+
+        let jar = zip::Reader::open(&main).unwrap();
+        let names = jar.entries().iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names[..4], ["META-INF/", "META-INF/MANIFEST.MF", "META-INF/INDEX.LIST", "com/"]);
+        assert_eq!(names.iter().filter(|n| **n == "META-INF/INDEX.LIST").count(), 1);
+
+        let index = String::from_utf8(jar.data(jar.find("META-INF/INDEX.LIST").unwrap()).unwrap()).unwrap();
+        assert_eq!(index, "JarIndex-Version: 1.0\n\nmain.jar\ncom/example\ncom/example/inner\n\nlib/dep.jar\nRoot.class\norg/dep\n\n");
+        assert_eq!(jar.data(jar.find("com/example/A.class").unwrap()).unwrap(), b"A");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test] fn manifest_wraps_at_72_bytes() {
+        let class_path = (0 .. 10).map(|i| format!("lib/dependency-{}.jar", i)).collect::<Vec<_>>();
+        let manifest = Manifest {
+            class_path:     class_path.clone(),
+            attributes:     vec![("X-Unicode".into(), "\u{E9}".repeat(40))],
+            sections:       vec![ManifestSection { name: "com/example/".into(), attributes: vec![("Sealed".into(), "true".into())], _non_exhaustive: () }],
+            ..Manifest::default()
+        };
+        let text = manifest.serialize().unwrap();
+        assert!(text.starts_with("Manifest-Version: 1.0\r\nClass-Path: lib/dependency-0.jar lib/dependency-1.jar lib/dependency-2.j\r\n ar lib/dependency-3.jar "), "{}", text);
+        assert!(text.ends_with("\r\n\r\nName: com/example/\r\nSealed: true\r\n\r\n"), "{}", text);
+        for line in text.split("\r\n") {
+            assert!(line.len() <= 72, "{:?}", line);
+            assert!(!line.contains('\n') && !line.contains('\r'));
+        }
+        // Multi-byte characters aren't split across lines
+        assert!(text.contains(&format!("X-Unicode: {}\r\n {}\r\n", "\u{E9}".repeat(30), "\u{E9}".repeat(10))), "{}", text);
+
+        let parsed = Manifest::parse(&text).unwrap();
+        assert_eq!(parsed.class_path, class_path);
+        assert_eq!(parsed.attribute("x-unicode"), Some("\u{E9}".repeat(40).as_str()));
+        assert_eq!(parsed.sections.len(), 1);
+        assert_eq!(parsed.sections[0].name, "com/example/");
+        assert_eq!(parsed.sections[0].attributes, [("Sealed".to_owned(), "true".to_owned())]);
+        assert_eq!(parsed.serialize().unwrap(), text);
+    }
+
+    #[test] fn manifest_parse() {
+        let manifest = Manifest::parse("Manifest-Version: 1.0\nMain-Class: com.exa\n mple.Main\nmulti-release: TRUE\nAdd-Opens: java.base/java.lang\n  java.base/java.util\nCreated-By:17\n\n\nName: a/\nX: y\n").unwrap();
+        assert_eq!(manifest.main_class.as_deref(), Some("com.example.Main"));
+        assert!(manifest.multi_release);
+        assert_eq!(manifest.add_opens, ["java.base/java.lang", "java.base/java.util"]);
+        assert_eq!(manifest.attributes, [("Created-By".to_owned(), "17".to_owned())]);
+        assert_eq!(manifest.sections.len(), 1);
+        assert_eq!(manifest.sections[0].attributes, [("X".to_owned(), "y".to_owned())]);
+
+        for invalid in [" continuation\r\n", "No colon\r\n", "-Bad: name\r\n", "Main-Class: A\r\n\r\nNot-Name: a/\r\n"].iter() {
+            assert_eq!(Manifest::parse(invalid).unwrap_err().kind(), ErrorKind::InvalidData, "{:?}", invalid);
+        }
+    }
+
+    #[test] fn manifest_serialize_errors() {
+        let invalid_name = Manifest { attributes: vec![("Bad Name".into(), "value".into())], ..Manifest::default() };
+        assert_eq!(invalid_name.serialize().unwrap_err().kind(), ErrorKind::InvalidInput);
+        let newline = Manifest { main_class: Some("com.example.A\nClass-Path: evil.jar".into()), ..Manifest::default() };
+        assert_eq!(newline.serialize().unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test] fn manifest_serialize_rejects_typed_attributes() {
+        for name in ["Main-Class", "main-class", "MULTI-RELEASE", "Class-Path", "Manifest-Version"].iter() {
+            let duplicate = Manifest { main_class: Some("com.example.Main".into()), attributes: vec![(name.to_string(), "value".into())], ..Manifest::default() };
+            let err = duplicate.serialize().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{}", name);
+            assert!(err.to_string().contains(name), "{}", err);
+        }
+
+        // Per-entry sections may repeat main section attribute names
+        let section = Manifest {
+            multi_release:  true,
+            sections:       vec![ManifestSection { name: "com/example/".into(), attributes: vec![("Multi-Release".into(), "true".into())], _non_exhaustive: () }],
+            ..Manifest::default()
+        };
+        assert_eq!(Manifest::parse(&section.serialize().unwrap()).unwrap(), section);
+    }
+}
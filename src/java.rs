@@ -1,31 +1,56 @@
 //! `%JAVA_HOME%\bin\java` - Run `.jar` files
 
+use crate::tool;
 use std::io::{Error, ErrorKind};
 use std::path::{Path};
-use std::process::Command;
+use std::process::{Command, Output};
 
 /// std::io::[Result](https://doc.rust-lang.org/std/io/type.Result.html)
 pub type Result<T> = std::io::Result<T>;
 
+/// Launch a Java program as a subprocess
+///
+/// Exactly one of [main_class](#structfield.main_class) or [jar](#structfield.jar) must be set.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// let output = jerk::java::Run {
+///     classpaths: &[Path::new("hello.jar")],
+///     main_class: Some("com.example.Hello"),
+///     args:       &["world"],
+///     ..jerk::java::Run::default()
+/// }.output().unwrap();
+/// assert!(output.status.success());
+/// ```
 #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Run<'a> {
     pub java_home:          Option<&'a Path>,
 
-    pub files:              &'a [&'a Path],
+    /// Run `main_class.main(args)`
+    pub main_class:         Option<&'a str>,
+    /// Run the `Main-Class` of a `.jar` (`java -jar ...`)
+    pub jar:                Option<&'a Path>,
+    /// Arguments passed to the program's `main`
+    pub args:               &'a [&'a str],
+
     pub classpaths:         &'a [&'a Path],
     pub system_properties:  &'a [(&'a str, &'a str)],
+    /// Additional options passed to the JVM as-is, before the main class or `-jar`
+    pub jvm_options:        &'a [&'a str],
 
     pub verbose_class:      bool,
     pub verbose_gc:         bool,
     pub verbose_jni:        bool,
 
-    pub out_classes:        Option<&'a Path>,
-    pub out_sources:        Option<&'a Path>,
-    pub out_headers:        Option<&'a Path>,
-
+    /// `("", true)` enables assertions everywhere (`-ea`), `("com.example...", false)` disables them for a package (`-da:com.example...`), etc.
     pub enable_assertions:         &'a [(&'a str, bool)],
     pub enable_system_assertions:  Option<bool>,
 
+    /// Additional environment variables for the subprocess
+    pub env:                &'a [(&'a str, &'a str)],
+    /// Working directory for the subprocess
+    pub current_dir:        Option<&'a Path>,
+
     #[doc(hidden)] pub _non_exhaustive: (),
 }
 
@@ -34,19 +59,31 @@ impl<'a> Run<'a> {
         Default::default()
     }
 
+    /// Run with inherited stdin/stdout/stderr, failing if the program doesn't exit successfully.
     pub fn run(&self) -> Result<()> {
-        let mut java_home_buf = None;
-        let java_home = self.java_home.or_else(||{
-            java_home_buf = Some(crate::search::find_java_home()?);
-            java_home_buf.as_ref().map(|p| &**p)
-        }).ok_or_else(||
-            Error::new(ErrorKind::NotFound, "JAVA_HOME not set and could not be found, unable to run")
-        )?;
+        let status = self.command()?.status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::Other, format!("java ... failed: {:?}", status)))
+        }
+    }
+
+    /// Run to completion, capturing stdout and stderr.  A non-zero exit status is *not* an error.
+    pub fn output(&self) -> Result<Output> {
+        self.command()?.output()
+    }
+
+    pub fn command(&self) -> Result<Command> {
+        let java_home = tool::java_home(self.java_home)?;
 
         let mut cmd = Command::new(java_home.join("bin/java"));
         // -d32 -d64 -server
 
-        for classpath in self.classpaths    { cmd.arg("-cp").arg(classpath); }
+        if !self.classpaths.is_empty() {
+            let classpath = std::env::join_paths(self.classpaths.iter()).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+            cmd.arg("-cp").arg(classpath);
+        }
         for (k,v) in self.system_properties { cmd.arg(format!("-D{}={}", k, v)); }
 
         for (flag, cond) in [
@@ -59,6 +96,29 @@ impl<'a> Run<'a> {
             }
         }
 
-        unimplemented!();
+        for (package, enable) in self.enable_assertions {
+            let flag = if *enable { "-ea" } else { "-da" };
+            if package.is_empty() { cmd.arg(flag); } else { cmd.arg(format!("{}:{}", flag, package)); }
+        }
+        match self.enable_system_assertions {
+            None        => {},
+            Some(true)  => { cmd.arg("-esa"); },
+            Some(false) => { cmd.arg("-dsa"); },
+        }
+
+        cmd.args(self.jvm_options);
+
+        match (self.main_class, self.jar) {
+            (Some(main_class), None)    => { cmd.arg(main_class); },
+            (None, Some(jar))           => { cmd.arg("-jar").arg(jar); },
+            (Some(_), Some(_))          => return Err(Error::new(ErrorKind::InvalidInput, "java::Run: main_class and jar are mutually exclusive")),
+            (None, None)                => return Err(Error::new(ErrorKind::InvalidInput, "java::Run: either main_class or jar must be set")),
+        }
+        cmd.args(self.args);
+
+        for (k,v) in self.env { cmd.env(k, v); }
+        if let Some(dir) = self.current_dir { cmd.current_dir(dir); }
+
+        Ok(cmd)
     }
 }
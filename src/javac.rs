@@ -1,8 +1,12 @@
 //! `%JAVA_HOME%\bin\javac` - Compile `.class` files from `.java` files
 
+use crate::{argfile, jni, jvm, tool};
+use jni_sys::*;
+use std::ffi::OsString;
 use std::io::{Error, ErrorKind};
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::ptr::null_mut;
 
 /// std::io::[Result](https://doc.rust-lang.org/std/io/type.Result.html)
 pub type Result<T> = std::io::Result<T>;
@@ -28,8 +32,14 @@ pub struct Compile {
     pub out_headers:                Option<PathBuf>,
     // -implicit
     // -encoding
-    // -source <release>
-    // -target <release>
+    /// `-source N` - accept source code compatible with Java N
+    pub source:                     Option<u32>,
+    /// `-target N` - generate class files for Java N
+    pub target:                     Option<u32>,
+    /// `--release N` - compile against the public API of Java N (javac 9+).  Can't be combined with [boot_class_paths](#structfield.boot_class_paths) or [system](#structfield.system).
+    pub release:                    Option<u32>,
+    /// `--system <jdk | none>` - where to find system modules (javac 9+).  `"none"` compiles without the JDK's class library, e.g. against `android.jar` on the classpath.
+    pub system:                     Option<PathBuf>,
     // -profile <profile>
     // -version
     // -help
@@ -38,6 +48,9 @@ pub struct Compile {
 
     pub files:                      Vec<PathBuf>,
 
+    /// Compile inside a JVM loaded into this process via `javax.tools.ToolProvider` instead of spawning `bin/javac`, when possible.
+    pub in_process:                 bool,
+
     #[doc(hidden)] pub _non_exhaustive: (),
 }
 
@@ -60,8 +73,18 @@ impl Compile {
         Default::default()
     }
 
+    /// Compile in-process if [in_process](#structfield.in_process) is set and supported, otherwise run `bin/javac` (via an `@argfile` if the arguments are too long.)
     pub fn exec(&self) -> Result<()> {
-        let status = self.command()?.status()?;
+        if self.in_process {
+            match self.exec_in_process() {
+                Err(err) if err.kind() == ErrorKind::Unsupported => {}, // fall back to bin/javac
+                other => return other,
+            }
+        }
+
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("javac"));
+        let _argfile = argfile::args(&mut cmd, self.args()?)?;
+        let status = cmd.status()?;
         if status.success() {
             Ok(())
         } else {
@@ -69,16 +92,80 @@ impl Compile {
         }
     }
 
+    /// Compile using `javax.tools.ToolProvider.getSystemJavaCompiler()` inside a JVM loaded into this process.
+    ///
+    /// Diagnostics are gathered by a `javax.tools.DiagnosticCollector` and written to stderr, like `bin/javac` would.
+    /// Fails with [ErrorKind::Unsupported] if no in-process compiler is available (no JVM library, JRE only, non-Unicode arguments, etc.)
+    pub fn exec_in_process(&self) -> Result<()> {
+        let java_home = tool::java_home(self.java_home.as_deref())?;
+        let options = self.options()?.into_iter().map(into_string).collect::<Result<Vec<String>>>()?;
+        let files   = self.files.iter().map(|f| into_string(f.clone().into_os_string())).collect::<Result<Vec<String>>>()?;
+        let env = in_process_env(&java_home)?;
+
+        // Safety:
+        // * `env` is attached to the current thread by in_process_env
+        // * all names and signatures are `\0` terminated
+        unsafe {
+            let _frame = jni::LocalFrame::push(env, 64)?;
+
+            let tool_provider = jni::find_class(env, "javax/tools/ToolProvider\0").map_err(unsupported)?;
+            let get_compiler = jni::static_method(env, tool_provider, "getSystemJavaCompiler\0", "()Ljavax/tools/JavaCompiler;\0").map_err(unsupported)?;
+            let compiler = (**env).CallStaticObjectMethodA.unwrap()(env, tool_provider, get_compiler, [].as_ptr());
+            let compiler = jni::non_null(env, compiler, "system Java compiler").map_err(unsupported)?;
+
+            let collector_class = jni::find_class(env, "javax/tools/DiagnosticCollector\0")?;
+            let collector       = jni::new_object(env, collector_class, "()V\0", &[])?;
+            let file_manager    = jni::call_object(env, compiler, "getStandardFileManager\0", "(Ljavax/tools/DiagnosticListener;Ljava/util/Locale;Ljava/nio/charset/Charset;)Ljavax/tools/StandardJavaFileManager;\0", &[
+                jvalue { l: collector }, jvalue { l: null_mut() }, jvalue { l: null_mut() },
+            ])?;
+            let files           = jni::new_string_list(env, files.iter().map(|f| f.as_str()))?;
+            let units           = jni::call_object(env, file_manager, "getJavaFileObjectsFromStrings\0", "(Ljava/lang/Iterable;)Ljava/lang/Iterable;\0", &[jvalue { l: files }])?;
+            let options         = jni::new_string_list(env, options.iter().map(|o| o.as_str()))?;
+            let task            = jni::call_object(env, compiler, "getTask\0", "(Ljava/io/Writer;Ljavax/tools/JavaFileManager;Ljavax/tools/DiagnosticListener;Ljava/lang/Iterable;Ljava/lang/Iterable;Ljava/lang/Iterable;)Ljavax/tools/JavaCompiler$CompilationTask;\0", &[
+                jvalue { l: null_mut() }, jvalue { l: file_manager }, jvalue { l: collector }, jvalue { l: options }, jvalue { l: null_mut() }, jvalue { l: units },
+            ])?;
+            let success         = jni::call_object(env, task, "call\0", "()Ljava/lang/Boolean;\0", &[])?;
+            let success         = jni::call_bool(env, success, "booleanValue\0", "()Z\0", &[])?;
+            let _               = jni::call_void(env, file_manager, "close\0", "()V\0", &[]);
+
+            let diagnostics     = jni::call_object(env, collector, "getDiagnostics\0", "()Ljava/util/List;\0", &[])?;
+            let mut errors = 0;
+            for i in 0 .. jni::call_int(env, diagnostics, "size\0", "()I\0", &[])? {
+                let diagnostic  = jni::call_object(env, diagnostics, "get\0", "(I)Ljava/lang/Object;\0", &[jvalue { i }])?;
+                let kind        = jni::call_object(env, diagnostic, "getKind\0", "()Ljavax/tools/Diagnostic$Kind;\0", &[])?;
+                if jni::to_string(env, kind)? == "ERROR" { errors += 1; }
+                eprintln!("{}", jni::to_string(env, diagnostic)?);
+                (**env).DeleteLocalRef.unwrap()(env, kind);
+                (**env).DeleteLocalRef.unwrap()(env, diagnostic);
+            }
+
+            if success {
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::Other, format!("javac (in-process) ... failed: {} error(s)", errors)))
+            }
+        }
+    }
+
+    /// Build a `bin/javac` command with every argument on the command line.
+    ///
+    /// Unlike [exec](#method.exec), this never uses an `@argfile`, so it may exceed command line length limits for large projects.
     pub fn command(&self) -> Result<Command> {
-        let mut java_home_buf = None;
-        let java_home = self.java_home.as_ref().or_else(||{
-            java_home_buf = Some(crate::search::find_java_home()?);
-            java_home_buf.as_ref()
-        }).ok_or_else(||
-            Error::new(ErrorKind::NotFound, "JAVA_HOME not set and could not be found, unable to run")
-        )?;
-
-        let mut cmd = Command::new(java_home.join("bin").join("javac"));
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("javac"));
+        cmd.args(self.args()?);
+        Ok(cmd)
+    }
+
+    /// All command line arguments, including [files](#structfield.files)
+    fn args(&self) -> Result<Vec<OsString>> {
+        let mut args = self.options()?;
+        args.extend(self.files.iter().map(|f| f.clone().into_os_string()));
+        Ok(args)
+    }
+
+    /// All command line options, excluding [files](#structfield.files)
+    fn options(&self) -> Result<Vec<OsString>> {
+        let mut cmd = tool::Args(Vec::new());
         match self.debug_info {
             None => {},
             Some(DebugInfo::NONE)   => { cmd.arg("-g:none"); },
@@ -96,16 +183,27 @@ impl Compile {
             }
         }
 
-        for p in self.class_paths.iter()      { cmd.arg("-cp").arg(p); }
-        for p in self.source_paths.iter()     { cmd.arg("-sourcepath").arg(p); }
-        for p in self.boot_class_paths.iter() { cmd.arg("-bootclasspath").arg(p); }
-        for p in self.extension_dirs.iter()   { cmd.arg("-extdirs").arg(p); }
+        if let Some(source)  = self.source  { cmd.arg("-source").arg(source.to_string()); }
+        if let Some(target)  = self.target  { cmd.arg("-target").arg(target.to_string()); }
+        if let Some(release) = self.release { cmd.arg("--release").arg(release.to_string()); }
+        if let Some(system)  = self.system.as_ref() { cmd.arg("--system").arg(system); }
+
+        // javac only honors the last of each of these, so multiple paths must be joined into a single argument
+        for (flag, paths) in [
+            ("-cp",             &self.class_paths),
+            ("-sourcepath",     &self.source_paths),
+            ("-bootclasspath",  &self.boot_class_paths),
+            ("-extdirs",        &self.extension_dirs),
+            ("-processorpath",  &self.annotation_processor_paths),
+        ].iter().copied() {
+            if !paths.is_empty() {
+                cmd.arg(flag).arg(std::env::join_paths(paths.iter()).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?);
+            }
+        }
 
         let processors = self.annotation_processors.join(",");
         if processors.len() != 0 { cmd.arg("-processors").arg(processors); }
 
-        for p in self.annotation_processor_paths.iter() { cmd.arg("-processorpath").arg(p); }
-
         for (flag, dir) in [
             ("-d", self.out_classes.as_ref()),
             ("-s", self.out_sources.as_ref()),
@@ -127,8 +225,53 @@ impl Compile {
         }
 
         for (k,v) in self.annotation_parameters.iter() { cmd.arg(format!("-A{}={}", k, v)); }
-        for file in self.files.iter() { cmd.arg(file); }
 
-        Ok(cmd)
+        Ok(cmd.0)
+    }
+}
+
+/// Load a JVM into this process (or reuse an existing one) and attach the current thread to it.
+fn in_process_env(java_home: &Path) -> Result<*mut JNIEnv> {
+    let library = jvm::Library::from_already_loaded()
+        .or_else(|_| jvm::Library::from_java_home(java_home))
+        .map_err(unsupported)?;
+
+    let vm = match library.create_java_vm(Vec::new()) {
+        Ok(vm) => vm,
+        Err(jvm::JniError::EXIST) => library.get_created_java_vms().map_err(unsupported)?.into_iter().next()
+            .ok_or_else(|| unsupported("JNI_EEXIST error creating Java VM, but unable to get an existing VM"))?,
+        Err(err) => return Err(unsupported(err)),
+    };
+
+    let mut env = null_mut();
+    match unsafe { (**vm).AttachCurrentThread.unwrap()(vm, &mut env, null_mut()) } {
+        JNI_OK  => Ok(env as *mut JNIEnv),
+        err     => Err(unsupported(format!("AttachCurrentThread failed: {}", err))),
+    }
+}
+
+fn into_string(arg: OsString) -> Result<String> {
+    arg.into_string().map_err(|arg| unsupported(format!("non-Unicode javac argument: {:?}", arg)))
+}
+
+fn unsupported(err: impl ToString) -> Error {
+    Error::new(ErrorKind::Unsupported, err.to_string())
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn path_lists_are_joined() {
+        let compile = Compile {
+            class_paths:        vec!["junit.jar".into(), "hamcrest.jar".into()],
+            boot_class_paths:   vec!["android.jar".into()],
+            ..Compile::default()
+        };
+        let options = compile.options().unwrap();
+        let joined = std::env::join_paths(["junit.jar", "hamcrest.jar"].iter()).unwrap();
+        assert_eq!(options.iter().filter(|option| *option == "-cp").count(), 1);
+        assert!(options.windows(2).any(|pair| pair[0] == "-cp" && pair[1] == joined));
+        assert!(options.windows(2).any(|pair| pair[0] == "-bootclasspath" && pair[1] == "android.jar"));
+        assert!(!options.iter().any(|option| option == "-sourcepath"));
     }
 }
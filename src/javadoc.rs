@@ -0,0 +1,105 @@
+//! `%JAVA_HOME%\bin\javadoc` - Generate HTML documentation from `.java` files
+
+use crate::{argfile, tool};
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// std::io::[Result](https://doc.rust-lang.org/std/io/type.Result.html)
+pub type Result<T> = std::io::Result<T>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Document {
+    pub java_home:                  Option<PathBuf>,
+    pub quiet:                      bool,
+    pub verbose:                    bool,
+    pub class_paths:                Vec<PathBuf>,
+    pub source_paths:               Vec<PathBuf>,
+    /// `-bootclasspath` - document against these instead of the JDK's class library, e.g. `android.jar`.  Requires [source](#structfield.source) `8` or older on javadoc 9+.
+    pub boot_class_paths:           Vec<PathBuf>,
+    /// `-source N` - accept source code compatible with Java N
+    pub source:                     Option<u32>,
+    /// `--release N` - document against the public API of Java N (javadoc 9+)
+    pub release:                    Option<u32>,
+    /// `-Xdoclint:...` - e.g. `"none"`, `"all"`, or `"all,-missing"`.  `None` uses javadoc's default checks.
+    pub doclint:                    Option<String>,
+    /// `-link <url>` - link to existing documentation that javadoc can fetch `package-list`/`element-list` from
+    pub links:                      Vec<String>,
+    /// `-linkoffline <url> <dir>` - link to documentation at `url`, using the `package-list`/`element-list` found in `dir`
+    pub links_offline:              Vec<(String, PathBuf)>,
+    pub window_title:               Option<String>,
+    pub doc_title:                  Option<String>,
+    pub out_dir:                    Option<PathBuf>,
+    // -private / -package / -protected / -public
+    // -encoding / -docencoding / -charset
+    // -overview <file>
+
+    pub files:                      Vec<PathBuf>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Run `bin/javadoc` (via an `@argfile` if the arguments are too long.)
+    pub fn exec(&self) -> Result<()> {
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("javadoc"));
+        let _argfile = argfile::args(&mut cmd, self.args()?)?;
+        let status = cmd.status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::Other, format!("javadoc ... failed: {:?}", status)))
+        }
+    }
+
+    /// Build a `bin/javadoc` command with every argument on the command line.
+    ///
+    /// Unlike [exec](#method.exec), this never uses an `@argfile`, so it may exceed command line length limits for large projects.
+    pub fn command(&self) -> Result<Command> {
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("javadoc"));
+        cmd.args(self.args()?);
+        Ok(cmd)
+    }
+
+    /// All command line arguments, including [files](#structfield.files)
+    fn args(&self) -> Result<Vec<OsString>> {
+        let mut cmd = tool::Args(Vec::new());
+
+        for (flag, cond) in [
+            ("-quiet",      self.quiet),
+            ("-verbose",    self.verbose),
+        ].iter().copied() {
+            if cond { cmd.arg(flag); }
+        }
+
+        if let Some(source)  = self.source  { cmd.arg("-source").arg(source.to_string()); }
+        if let Some(release) = self.release { cmd.arg("--release").arg(release.to_string()); }
+        if let Some(doclint) = self.doclint.as_ref() { cmd.arg(format!("-Xdoclint:{}", doclint)); }
+
+        for (flag, paths) in [
+            ("-classpath",      &self.class_paths),
+            ("-sourcepath",     &self.source_paths),
+            ("-bootclasspath",  &self.boot_class_paths),
+        ].iter().copied() {
+            if !paths.is_empty() {
+                cmd.arg(flag).arg(std::env::join_paths(paths.iter()).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?);
+            }
+        }
+
+        for url in self.links.iter() { cmd.arg("-link").arg(url); }
+        for (url, dir) in self.links_offline.iter() { cmd.arg("-linkoffline").arg(url).arg(dir); }
+
+        if let Some(title) = self.window_title.as_ref() { cmd.arg("-windowtitle").arg(title); }
+        if let Some(title) = self.doc_title.as_ref()    { cmd.arg("-doctitle").arg(title); }
+        if let Some(dir) = self.out_dir.as_ref()        { cmd.arg("-d").arg(dir); }
+
+        for f in self.files.iter() { cmd.arg(f); }
+
+        Ok(cmd.0)
+    }
+}
@@ -0,0 +1,134 @@
+//! `%JAVA_HOME%\bin\jdeps` - Analyze `.jar` and `.class` dependencies
+
+use crate::{paths, tool};
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// std::io::[Result](https://doc.rust-lang.org/std/io/type.Result.html)
+pub type Result<T> = std::io::Result<T>;
+
+/// Analyze the dependencies of `.jar`s, `.class` files, or directories of them
+///
+/// ```no_run
+/// let modules = jerk::jdeps::Analyze {
+///     files: vec!["hello.jar".into()],
+///     ..jerk::jdeps::Analyze::default()
+/// }.module_deps().unwrap();
+/// assert!(modules.iter().any(|m| m == "java.base"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Analyze {
+    pub java_home:              Option<PathBuf>,
+    pub class_paths:            Vec<PathBuf>,
+    pub module_paths:           Vec<PathBuf>,
+    /// `--multi-release <version>` - which `META-INF/versions/N` classes of multi-release jars to analyze, e.g. `"base"` or `"11"`.
+    /// Defaults to the feature version of the JDK when analyzing [module_deps](#method.module_deps).
+    pub multi_release:          Option<String>,
+    /// `--ignore-missing-deps` - don't fail because of classes that can't be found (e.g. optional dependencies not on the classpath)
+    pub ignore_missing_deps:    bool,
+    pub recursive:              bool,
+    pub verbose:                bool,
+
+    pub files:                  Vec<PathBuf>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Analyze {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Run `bin/jdeps` with inherited stdio, printing a summary of dependencies.
+    pub fn exec(&self) -> Result<()> {
+        let status = self.command()?.status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::Other, format!("jdeps ... failed: {:?}", status)))
+        }
+    }
+
+    /// Get the JDK modules required by [files](#structfield.files), as listed by `jdeps --print-module-deps` (JDK 11+.)
+    ///
+    /// The result is suitable for [jlink::Link::add_modules](../jlink/struct.Link.html#structfield.add_modules).
+    pub fn module_deps(&self) -> Result<Vec<String>> {
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("jdeps"));
+        cmd.arg("--print-module-deps");
+        if self.multi_release.is_none() {
+            if let Ok(version) = paths::java_feature_version(&tool::java_home(self.java_home.as_deref())?) { cmd.arg("--multi-release").arg(version.to_string()); }
+        }
+        cmd.args(self.args()?);
+
+        let output = cmd.output()?;
+        if !output.status.success() {
+            return Err(Error::new(ErrorKind::Other, format!("jdeps --print-module-deps ... failed: {:?}\n{}", output.status, String::from_utf8_lossy(&output.stderr))));
+        }
+        let stdout = String::from_utf8(output.stdout).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        // Warnings may precede the module list, which is always the last line
+        let modules = stdout.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("");
+        Ok(modules.split(',').map(str::trim).filter(|m| !m.is_empty()).map(String::from).collect())
+    }
+
+    pub fn command(&self) -> Result<Command> {
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("jdeps"));
+        cmd.args(self.args()?);
+        Ok(cmd)
+    }
+
+    /// All command line arguments, including [files](#structfield.files)
+    fn args(&self) -> Result<Vec<OsString>> {
+        let mut cmd = tool::Args(Vec::new());
+
+        for (flag, paths) in [
+            ("--class-path",    &self.class_paths),
+            ("--module-path",   &self.module_paths),
+        ].iter().copied() {
+            if !paths.is_empty() {
+                cmd.arg(flag).arg(std::env::join_paths(paths.iter()).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?);
+            }
+        }
+
+        if let Some(version) = self.multi_release.as_ref() { cmd.arg("--multi-release").arg(version); }
+
+        for (flag, cond) in [
+            ("--ignore-missing-deps",   self.ignore_missing_deps),
+            ("--recursive",             self.recursive),
+            ("-verbose",                self.verbose),
+        ].iter().copied() {
+            if cond { cmd.arg(flag); }
+        }
+
+        for f in self.files.iter() { cmd.arg(f); }
+
+        Ok(cmd.0)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn args() {
+        assert!(Analyze::default().args().unwrap().is_empty());
+
+        let args = Analyze {
+            class_paths:            vec!["a.jar".into(), "b.jar".into()],
+            multi_release:          Some("base".into()),
+            ignore_missing_deps:    true,
+            verbose:                true,
+            files:                  vec!["hello.jar".into(), "classes".into()],
+            ..Analyze::default()
+        }.args().unwrap();
+        let mut expected = vec![OsString::from("--class-path"), std::env::join_paths(["a.jar", "b.jar"].iter()).unwrap()];
+        expected.extend(["--multi-release", "base", "--ignore-missing-deps", "-verbose", "hello.jar", "classes"].iter().map(OsString::from));
+        assert_eq!(args, expected);
+    }
+
+    #[test] fn args_invalid_path() {
+        let separator = if cfg!(windows) { ";" } else { ":" };
+        let err = Analyze { module_paths: vec![format!("a{}b", separator).into()], ..Analyze::default() }.args().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,137 @@
+//! `%JAVA_HOME%\bin\jlink` - Assemble minimal Java runtime images
+
+use crate::{jdeps, paths, tool};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// std::io::[Result](https://doc.rust-lang.org/std/io/type.Result.html)
+pub type Result<T> = std::io::Result<T>;
+
+/// Link a set of modules into a runtime image (JDK 9+)
+///
+/// The resulting image is laid out like a regular `JAVA_HOME`, including `bin/java` and `jvm.dll` / `libjvm.so` / `libjvm.dylib`,
+/// so it can be loaded with [jvm::Library::from_java_home](../jvm/struct.Library.html#method.from_java_home).
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Link {
+    pub java_home:          Option<PathBuf>,
+    /// `--module-path` - where to find modules.  The JDK's own `jmods` are used if empty (JDK 11+ implicitly, JDK 9-10 require this.)
+    pub module_paths:       Vec<PathBuf>,
+    /// `--add-modules` - root modules to include (along with their transitive dependencies.)
+    pub add_modules:        Vec<String>,
+    /// `--bind-services` - also include service provider modules
+    pub bind_services:      bool,
+    pub strip_debug:        bool,
+    pub no_header_files:    bool,
+    pub no_man_pages:       bool,
+    /// `--compress=...` - e.g. `"2"` (JDK 9-20) or `"zip-6"` (JDK 21+)
+    pub compress:           Option<String>,
+    pub verbose:            bool,
+    /// `--output` - the directory to create.  `jlink` fails if it already exists.
+    pub output:             Option<PathBuf>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Link {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn exec(&self) -> Result<()> {
+        let status = self.command()?.status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::Other, format!("jlink ... failed: {:?}", status)))
+        }
+    }
+
+    pub fn command(&self) -> Result<Command> {
+        let mut cmd = Command::new(tool::java_home(self.java_home.as_deref())?.join("bin").join("jlink"));
+        cmd.args(self.args()?);
+        Ok(cmd)
+    }
+
+    fn args(&self) -> Result<Vec<OsString>> {
+        let mut cmd = tool::Args(Vec::new());
+
+        if !self.module_paths.is_empty() {
+            cmd.arg("--module-path").arg(std::env::join_paths(self.module_paths.iter()).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?);
+        }
+        if !self.add_modules.is_empty() { cmd.arg("--add-modules").arg(self.add_modules.join(",")); }
+
+        for (flag, cond) in [
+            ("--bind-services",     self.bind_services),
+            ("--strip-debug",       self.strip_debug),
+            ("--no-header-files",   self.no_header_files),
+            ("--no-man-pages",      self.no_man_pages),
+            ("--verbose",           self.verbose),
+        ].iter().copied() {
+            if cond { cmd.arg(flag); }
+        }
+
+        if let Some(compress) = self.compress.as_ref() { cmd.arg(format!("--compress={}", compress)); }
+        if let Some(output) = self.output.as_ref() { cmd.arg("--output").arg(output); }
+
+        Ok(cmd.0)
+    }
+}
+
+/// Create a minimal runtime image at `output` containing only the JDK modules `jars` depend on.
+///
+/// Any existing directory at `output` is replaced.  Returns the modules that were included.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// let runtime = Path::new("target/runtime");
+/// jerk::jlink::minimal_runtime(None, &[Path::new("hello.jar")], runtime).unwrap();
+///
+/// let jvm = jerk::jvm::Library::from_java_home(runtime).unwrap();
+/// ```
+pub fn minimal_runtime(java_home: Option<&Path>, jars: &[&Path], output: &Path) -> Result<Vec<String>> {
+    let modules = jdeps::Analyze {
+        java_home:              java_home.map(PathBuf::from),
+        ignore_missing_deps:    true,
+        files:                  jars.iter().map(PathBuf::from).collect(),
+        ..jdeps::Analyze::default()
+    }.module_deps()?;
+
+    if output.exists() { fs::remove_dir_all(output)?; }
+    Link {
+        java_home:          java_home.map(PathBuf::from),
+        add_modules:        if modules.is_empty() { vec!["java.base".into()] } else { modules.clone() },
+        strip_debug:        true,
+        no_header_files:    true,
+        no_man_pages:       true,
+        output:             Some(output.into()),
+        ..Link::default()
+    }.exec()?;
+
+    paths::libjvm_dir(&output)?; // sanity check the image is loadable
+    Ok(modules)
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn args() {
+        assert!(Link::default().args().unwrap().is_empty());
+
+        let args = Link {
+            module_paths:       vec!["jmods".into()],
+            add_modules:        vec!["java.base".into(), "java.logging".into()],
+            strip_debug:        true,
+            no_man_pages:       true,
+            compress:           Some("zip-6".into()),
+            output:             Some("runtime".into()),
+            ..Link::default()
+        }.args().unwrap();
+        assert_eq!(args, [
+            "--module-path", "jmods", "--add-modules", "java.base,java.logging", "--strip-debug", "--no-man-pages",
+            "--compress=zip-6", "--output", "runtime",
+        ].iter().map(OsString::from).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,216 @@
+//! Crate internal JNI helpers
+//!
+//! All names and signatures passed to these functions must be `\0` terminated.
+//! Any pending Java exception is converted into an [io::Error] (and cleared) by the helpers that can throw.
+
+use jni_sys::*;
+use std::io;
+use std::ptr::null_mut;
+
+/// `PushLocalFrame` when created, `PopLocalFrame` when dropped.
+pub(crate) struct LocalFrame(*mut JNIEnv);
+
+impl LocalFrame {
+    pub unsafe fn push(env: *mut JNIEnv, capacity: jint) -> io::Result<Self> {
+        if (**env).PushLocalFrame.unwrap()(env, capacity) == JNI_OK {
+            Ok(Self(env))
+        } else {
+            check(env)?;
+            Err(io::Error::new(io::ErrorKind::Other, "PushLocalFrame failed"))
+        }
+    }
+}
+
+impl Drop for LocalFrame {
+    fn drop(&mut self) {
+        unsafe { (**self.0).PopLocalFrame.unwrap()(self.0, null_mut()) };
+    }
+}
+
+/// A global reference from `NewGlobalRef`, released with `DeleteGlobalRef` when dropped (attaching the current thread if needed.)
+///
+/// Unlike a `jobject`, this is `Send` + `Sync`:  global references are valid on every thread, until deleted.
+pub(crate) struct GlobalRef {
+    vm:     *mut JavaVM,
+    object: jobject,
+}
+
+// Global references (unlike local ones) may be used and deleted from any thread attached to `vm`
+unsafe impl Send for GlobalRef {}
+unsafe impl Sync for GlobalRef {}
+
+impl GlobalRef {
+    /// `NewGlobalRef(object)`.  `object` must not be null.  The Java VM must outlive the returned reference.
+    pub unsafe fn new(env: *mut JNIEnv, object: jobject) -> io::Result<Self> {
+        let mut vm = null_mut();
+        if (**env).GetJavaVM.unwrap()(env, &mut vm) != JNI_OK || vm.is_null() { return Err(io::Error::new(io::ErrorKind::Other, "GetJavaVM failed")); }
+        let object = (**env).NewGlobalRef.unwrap()(env, object);
+        check(env)?;
+        if object.is_null() { return Err(io::Error::new(io::ErrorKind::Other, "NewGlobalRef failed")); }
+        Ok(Self { vm, object })
+    }
+
+    pub fn as_raw(&self) -> jobject { self.object }
+
+    /// `DeleteGlobalRef` with `env`, the current thread's `JNIEnv`, instead of attaching to delete it.
+    pub unsafe fn delete(self, env: *mut JNIEnv) {
+        (**env).DeleteGlobalRef.unwrap()(env, self.object);
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for GlobalRef {
+    fn drop(&mut self) {
+        let object = self.object;
+        let _ = unsafe { crate::jvm::with_env(self.vm, |env| (**env).DeleteGlobalRef.unwrap()(env, object)) };
+    }
+}
+
+/// Convert any pending Java exception into an [io::Error], clearing it.
+pub(crate) unsafe fn check(env: *mut JNIEnv) -> io::Result<()> {
+    if (**env).ExceptionCheck.unwrap()(env) != JNI_TRUE { return Ok(()); }
+
+    let throwable = (**env).ExceptionOccurred.unwrap()(env);
+    (**env).ExceptionClear.unwrap()(env);
+    let message = to_string(env, throwable).unwrap_or_else(|_| String::from("java.lang.Throwable (toString() threw another exception)"));
+    (**env).DeleteLocalRef.unwrap()(env, throwable);
+    Err(io::Error::new(io::ErrorKind::Other, message))
+}
+
+/// [check] for exceptions, then fail with `ErrorKind::NotFound` if `value` is null.
+pub(crate) unsafe fn non_null<T>(env: *mut JNIEnv, value: *mut T, what: &str) -> io::Result<*mut T> {
+    check(env)?;
+    if value.is_null() {
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", what.trim_end_matches('\0'))))
+    } else {
+        Ok(value)
+    }
+}
+
+/// `FindClass`, where `name` looks like `"java/lang/String\0"`.
+pub(crate) unsafe fn find_class(env: *mut JNIEnv, name: &str) -> io::Result<jclass> {
+    assert!(name.ends_with('\0'));
+    let class = (**env).FindClass.unwrap()(env, name.as_ptr() as *const _);
+    non_null(env, class, name)
+}
+
+/// `GetMethodID`
+pub(crate) unsafe fn method(env: *mut JNIEnv, class: jclass, name: &str, sig: &str) -> io::Result<jmethodID> {
+    assert!(name.ends_with('\0') && sig.ends_with('\0'));
+    let method = (**env).GetMethodID.unwrap()(env, class, name.as_ptr() as *const _, sig.as_ptr() as *const _);
+    non_null(env, method, name)
+}
+
+/// `GetStaticMethodID`
+pub(crate) unsafe fn static_method(env: *mut JNIEnv, class: jclass, name: &str, sig: &str) -> io::Result<jmethodID> {
+    assert!(name.ends_with('\0') && sig.ends_with('\0'));
+    let method = (**env).GetStaticMethodID.unwrap()(env, class, name.as_ptr() as *const _, sig.as_ptr() as *const _);
+    non_null(env, method, name)
+}
+
+/// `NewObjectA` using the constructor of `class` matching `sig`.
+pub(crate) unsafe fn new_object(env: *mut JNIEnv, class: jclass, sig: &str, args: &[jvalue]) -> io::Result<jobject> {
+    let ctor = method(env, class, "<init>\0", sig)?;
+    let object = (**env).NewObjectA.unwrap()(env, class, ctor, args.as_ptr());
+    non_null(env, object, "<init>")
+}
+
+/// `CallObjectMethodA`, looking up `name` on the runtime class of `object`.
+pub(crate) unsafe fn call_object(env: *mut JNIEnv, object: jobject, name: &str, sig: &str, args: &[jvalue]) -> io::Result<jobject> {
+    let class = (**env).GetObjectClass.unwrap()(env, object);
+    let method = method(env, class, name, sig);
+    (**env).DeleteLocalRef.unwrap()(env, class);
+    let result = (**env).CallObjectMethodA.unwrap()(env, object, method?, args.as_ptr());
+    check(env)?;
+    Ok(result)
+}
+
+/// `CallObjectMethodA` for a `()Ljava/lang/String;` method, looking up `name` on the runtime class of `object`.  Returns `None` for `null`.
+pub(crate) unsafe fn call_string(env: *mut JNIEnv, object: jobject, name: &str) -> io::Result<Option<String>> {
+    let string = call_object(env, object, name, "()Ljava/lang/String;\0", &[])?;
+    if string.is_null() { return Ok(None); }
+    let value = get_string(env, string);
+    (**env).DeleteLocalRef.unwrap()(env, string);
+    Ok(Some(value))
+}
+
+/// `CallBooleanMethodA`, looking up `name` on the runtime class of `object`.
+pub(crate) unsafe fn call_bool(env: *mut JNIEnv, object: jobject, name: &str, sig: &str, args: &[jvalue]) -> io::Result<bool> {
+    let class = (**env).GetObjectClass.unwrap()(env, object);
+    let method = method(env, class, name, sig);
+    (**env).DeleteLocalRef.unwrap()(env, class);
+    let result = (**env).CallBooleanMethodA.unwrap()(env, object, method?, args.as_ptr());
+    check(env)?;
+    Ok(result == JNI_TRUE)
+}
+
+/// `CallIntMethodA`, looking up `name` on the runtime class of `object`.
+pub(crate) unsafe fn call_int(env: *mut JNIEnv, object: jobject, name: &str, sig: &str, args: &[jvalue]) -> io::Result<jint> {
+    let class = (**env).GetObjectClass.unwrap()(env, object);
+    let method = method(env, class, name, sig);
+    (**env).DeleteLocalRef.unwrap()(env, class);
+    let result = (**env).CallIntMethodA.unwrap()(env, object, method?, args.as_ptr());
+    check(env)?;
+    Ok(result)
+}
+
+/// `CallLongMethodA`, looking up `name` on the runtime class of `object`.
+pub(crate) unsafe fn call_long(env: *mut JNIEnv, object: jobject, name: &str, sig: &str, args: &[jvalue]) -> io::Result<jlong> {
+    let class = (**env).GetObjectClass.unwrap()(env, object);
+    let method = method(env, class, name, sig);
+    (**env).DeleteLocalRef.unwrap()(env, class);
+    let result = (**env).CallLongMethodA.unwrap()(env, object, method?, args.as_ptr());
+    check(env)?;
+    Ok(result)
+}
+
+/// `CallVoidMethodA`, looking up `name` on the runtime class of `object`.
+pub(crate) unsafe fn call_void(env: *mut JNIEnv, object: jobject, name: &str, sig: &str, args: &[jvalue]) -> io::Result<()> {
+    let class = (**env).GetObjectClass.unwrap()(env, object);
+    let method = method(env, class, name, sig);
+    (**env).DeleteLocalRef.unwrap()(env, class);
+    (**env).CallVoidMethodA.unwrap()(env, object, method?, args.as_ptr());
+    check(env)
+}
+
+/// `NewString` - goes through UTF-16 to avoid Java's "modified UTF-8" quirks (embedded nuls, supplementary characters.)
+pub(crate) unsafe fn new_string(env: *mut JNIEnv, value: &str) -> io::Result<jstring> {
+    let utf16 = value.encode_utf16().collect::<Vec<u16>>();
+    let string = (**env).NewString.unwrap()(env, utf16.as_ptr(), utf16.len() as _);
+    non_null(env, string, "NewString")
+}
+
+/// `GetStringChars` - invalid UTF-16 is replaced with U+FFFD.
+pub(crate) unsafe fn get_string(env: *mut JNIEnv, string: jstring) -> String {
+    if string.is_null() { return String::from("null"); }
+    let len = (**env).GetStringLength.unwrap()(env, string);
+    let chars = (**env).GetStringChars.unwrap()(env, string, null_mut());
+    if chars.is_null() { let _ = check(env); return String::new(); }
+    let value = String::from_utf16_lossy(std::slice::from_raw_parts(chars, len as usize));
+    (**env).ReleaseStringChars.unwrap()(env, string, chars);
+    value
+}
+
+/// `String.valueOf(object)`
+pub(crate) unsafe fn to_string(env: *mut JNIEnv, object: jobject) -> io::Result<String> {
+    if object.is_null() { return Ok(String::from("null")); }
+    let string = call_object(env, object, "toString\0", "()Ljava/lang/String;\0", &[])?;
+    let value = get_string(env, string);
+    (**env).DeleteLocalRef.unwrap()(env, string);
+    Ok(value)
+}
+
+/// `new java.util.ArrayList<String>(values)`
+pub(crate) unsafe fn new_string_list<'s>(env: *mut JNIEnv, values: impl IntoIterator<Item = &'s str>) -> io::Result<jobject> {
+    let class = find_class(env, "java/util/ArrayList\0")?;
+    let list = new_object(env, class, "()V\0", &[])?;
+    let add = method(env, class, "add\0", "(Ljava/lang/Object;)Z\0")?;
+    (**env).DeleteLocalRef.unwrap()(env, class);
+    for value in values {
+        let string = new_string(env, value)?;
+        (**env).CallBooleanMethodA.unwrap()(env, list, add, [jvalue { l: string }].as_ptr());
+        (**env).DeleteLocalRef.unwrap()(env, string);
+        check(env)?;
+    }
+    Ok(list)
+}
@@ -5,8 +5,9 @@ use crate::paths;
 use std::error::Error;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::io;
+use std::marker::PhantomData;
 use std::os::raw::c_void;
-use std::path::{Path};
+use std::path::{Path, PathBuf};
 use std::ptr::*;
 use jni_sys::*;
 #[cfg(unix)]    use libc::*;
@@ -186,24 +187,33 @@ impl Library {
         Ok(Self{jvm})
     }
 
-    /// `JNI_CreateJavaVM`
+    /// `JNI_CreateJavaVM` with `java_vm_options` passed as-is, and the highest JNI version the library supports.
+    ///
+    /// Fails with [JniError::INVAL](struct.JniError.html#associatedconstant.INVAL) if an option contains `\0`.
     #[cfg_attr(feature = "nightly", doc(cfg(not(target_os = "android"))))] // We actually still compile this in but discourage it as unlikely to work...
-    pub fn create_java_vm(&self, mut java_vm_options: Vec<String>) -> Result<*mut JavaVM, JniError> {
-        for o in java_vm_options.iter_mut() {
-            // XXX: Assert doesn't contain nul?  Escape to weird Java psuedo-UTF8 "nuls"?
-            o.push('\0');
-        }
+    pub fn create_java_vm(&self, java_vm_options: Vec<String>) -> Result<*mut JavaVM, JniError> {
+        self.create_java_vm_impl(&java_vm_options, None, false)
+    }
+
+    /// `JNI_CreateJavaVM` with typed [InitArgs](struct.InitArgs.html)
+    #[cfg_attr(feature = "nightly", doc(cfg(not(target_os = "android"))))] // We actually still compile this in but discourage it as unlikely to work...
+    pub fn create_vm_with(&self, args: &InitArgs) -> Result<Vm, JniError> {
+        let vm = self.create_java_vm_impl(&args.to_option_strings()?, args.version, args.ignore_unrecognized)?;
+        unsafe { Vm::from_raw(vm) }.ok_or(JniError(JNI_ERR))
+    }
 
-        let mut java_vm_options : Vec<JavaVMOption> = java_vm_options.iter_mut().map(|o| JavaVMOption {
-            optionString:   o.as_mut_ptr() as *mut _,
+    fn create_java_vm_impl(&self, java_vm_options: &[String], version: Option<jint>, ignore_unrecognized: bool) -> Result<*mut JavaVM, JniError> {
+        let java_vm_options = java_vm_options.iter().map(|o| std::ffi::CString::new(o.as_str())).collect::<Result<Vec<_>, _>>().map_err(|_| JniError::INVAL)?;
+        let mut java_vm_options : Vec<JavaVMOption> = java_vm_options.iter().map(|o| JavaVMOption {
+            optionString:   o.as_ptr() as *mut _,
             extraInfo:      null_mut(),
         }).collect();
 
         let mut args = JavaVMInitArgs {
-            version:            JNI_VERSION_1_6,
+            version:            version.unwrap_or_else(|| self.max_jni_version()),
             nOptions:           java_vm_options.len() as _,
             options:            java_vm_options.as_mut_ptr(),
-            ignoreUnrecognized: JNI_FALSE,
+            ignoreUnrecognized: if ignore_unrecognized { JNI_TRUE } else { JNI_FALSE },
         };
 
         let mut vm = null_mut();
@@ -216,6 +226,32 @@ impl Library {
         }
     }
 
+    /// The highest JNI version (e.g. `JNI_VERSION_10`) this library supports creating a Java VM with, as reported by `JNI_GetDefaultJavaVMInitArgs`.
+    pub fn max_jni_version(&self) -> jint {
+        JNI_VERSIONS.iter().copied().find(|&version| {
+            let mut args = JavaVMInitArgs { version, nOptions: 0, options: null_mut(), ignoreUnrecognized: JNI_FALSE };
+            unsafe { (self.jvm.JNI_GetDefaultJavaVMInitArgs)(&mut args as *mut _ as *mut _) == JNI_OK }
+        }).unwrap_or(JNI_VERSION_1_2)
+    }
+
+    /// `JNI_CreateJavaVM`, as a [Vm](struct.Vm.html) handle
+    #[cfg_attr(feature = "nightly", doc(cfg(not(target_os = "android"))))] // We actually still compile this in but discourage it as unlikely to work...
+    pub fn create_vm(&self, java_vm_options: Vec<String>) -> Result<Vm, JniError> {
+        let vm = self.create_java_vm(java_vm_options)?;
+        unsafe { Vm::from_raw(vm) }.ok_or(JniError(JNI_ERR))
+    }
+
+    /// `JNI_GetCreatedJavaVMs`, as [Vm](struct.Vm.html) handles
+    ///
+    /// # Safety
+    ///
+    /// The Java VMs may already be owned by someone else (another `Vm`, or a Java host that loaded this code), so see [Vm::from_raw].
+    ///
+    /// [Vm::from_raw]: struct.Vm.html#method.from_raw
+    pub unsafe fn get_created_vms(&self) -> Result<Vec<Vm>, JniError> {
+        Ok(self.get_created_java_vms()?.into_iter().filter_map(|vm| Vm::from_raw(vm)).collect())
+    }
+
     /// `JNI_GetCreatedJavaVMs`
     pub fn get_created_java_vms(&self) -> Result<Vec<*mut JavaVM>, JniError> {
         let mut vms = Vec::new();
@@ -251,3 +287,281 @@ impl Library {
         }
     }
 }
+
+/// JNI versions newer than `JNI_VERSION_1_2`, newest first.  Spelled out since older `jni-sys`es lack the newer constants.
+const JNI_VERSIONS : &[jint] = &[
+    0x0018_0000, // JNI_VERSION_24
+    0x0015_0000, // JNI_VERSION_21
+    0x0014_0000, // JNI_VERSION_20
+    0x0013_0000, // JNI_VERSION_19
+    0x000A_0000, // JNI_VERSION_10
+    0x0009_0000, // JNI_VERSION_9
+    JNI_VERSION_1_8,
+    JNI_VERSION_1_6,
+    JNI_VERSION_1_4,
+    JNI_VERSION_1_2,
+];
+
+/// Typed options for [Library::create_vm_with].
+///
+/// Strings containing `\0` are rejected with [JniError::INVAL] rather than silently truncated.
+///
+/// ```
+/// use jerk::jvm::InitArgs;
+/// let args = InitArgs {
+///     class_paths:        vec!["target/hello.jar".into()],
+///     system_properties:  vec![("com.example.mode".into(), "test".into())],
+///     max_heap:           Some("256m".into()),
+///     check_jni:          true,
+///     ..InitArgs::default()
+/// };
+/// assert_eq!(args.to_option_strings().unwrap(), ["-Djava.class.path=target/hello.jar", "-Dcom.example.mode=test", "-Xmx256m", "-Xcheck:jni"]);
+///
+/// assert!(InitArgs { options: vec!["-Dnul=\0".into()], ..InitArgs::default() }.to_option_strings().is_err());
+/// ```
+///
+/// [Library::create_vm_with]:  struct.Library.html#method.create_vm_with
+/// [JniError::INVAL]:          struct.JniError.html#associatedconstant.INVAL
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InitArgs {
+    /// `-Djava.class.path=...`, if not empty
+    pub class_paths:            Vec<PathBuf>,
+    /// `-Dkey=value`
+    pub system_properties:      Vec<(String, String)>,
+    /// `-Xms...` - e.g. `"64m"`
+    pub initial_heap:           Option<String>,
+    /// `-Xmx...` - e.g. `"1g"`
+    pub max_heap:               Option<String>,
+    /// `-javaagent:path[=options]` - Java agents (`.jar`s with a `Premain-Class`)
+    pub java_agents:            Vec<(PathBuf, Option<String>)>,
+    /// `-agentpath:path[=options]` - native JVMTI agents
+    pub native_agents:          Vec<(PathBuf, Option<String>)>,
+    /// `-Xcheck:jni` - validate JNI calls, at some cost in speed
+    pub check_jni:              bool,
+    /// `-Xrs` - reduce the Java VM's use of OS signals, leaving e.g. `SIGINT` / Ctrl+C handling to the host process
+    pub reduce_signals:         bool,
+    /// Any other options, passed as-is after the above
+    pub options:                Vec<String>,
+    /// `ignoreUnrecognized` - ignore unrecognized `-X` and `_` options instead of failing with `JNI_ERR` (default `false`)
+    pub ignore_unrecognized:    bool,
+    /// The JNI version to request, e.g. `JNI_VERSION_1_8`.  Defaults to the highest the [Library](struct.Library.html) supports.
+    pub version:                Option<jint>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl InitArgs {
+    /// The option strings passed to `JNI_CreateJavaVM`.
+    pub fn to_option_strings(&self) -> Result<Vec<String>, JniError> {
+        let mut options = Vec::new();
+        if !self.class_paths.is_empty() {
+            let classpath = std::env::join_paths(self.class_paths.iter()).map_err(|_| JniError::INVAL)?;
+            options.push(format!("-Djava.class.path={}", classpath.to_string_lossy()));
+        }
+        for (key, value) in self.system_properties.iter() { options.push(format!("-D{}={}", key, value)); }
+        if let Some(size) = self.initial_heap.as_ref()  { options.push(format!("-Xms{}", size)); }
+        if let Some(size) = self.max_heap.as_ref()      { options.push(format!("-Xmx{}", size)); }
+        for (flag, agents) in [("-javaagent:", &self.java_agents), ("-agentpath:", &self.native_agents)].iter() {
+            for (path, agent_options) in agents.iter() {
+                let mut option = format!("{}{}", flag, path.to_string_lossy());
+                if let Some(agent_options) = agent_options.as_ref() { option.push('='); option.push_str(agent_options); }
+                options.push(option);
+            }
+        }
+        if self.check_jni       { options.push("-Xcheck:jni".to_string()); }
+        if self.reduce_signals  { options.push("-Xrs".to_string()); }
+        options.extend(self.options.iter().cloned());
+
+        if options.iter().any(|option| option.contains('\0')) { return Err(JniError::INVAL); }
+        Ok(options)
+    }
+}
+
+/// The owner of a Java VM, as created by [Library::create_vm] or found by [Library::get_created_vms].
+///
+/// Unlike a `*mut JavaVM`, a `Vm` can be used from any thread (the JNI invocation API may be called from any thread.)
+/// It isn't `Clone`:  share it as a `&Vm` or `Arc<Vm>`, so [destroy](#method.destroy) can only be called once nothing else uses it.
+/// Dropping it does nothing:  a process generally can't create another Java VM after destroying one, so [destroy](#method.destroy)
+/// must be called explicitly if that's really what you want.
+///
+/// ```no_run
+/// let library = jerk::jvm::Library::get().unwrap();
+/// let vm = std::sync::Arc::new(library.create_vm(vec!["-Xcheck:jni".into()]).unwrap());
+/// let worker = {
+///     let vm = vm.clone();
+///     std::thread::spawn(move || {
+///         let guard = vm.attach_current_thread_as_daemon(Some("my-worker")).unwrap();
+///         let env = guard.env();
+///         // ...
+///     })
+/// };
+/// worker.join().unwrap();
+/// std::sync::Arc::try_unwrap(vm).unwrap().destroy().unwrap();
+/// ```
+///
+/// [Library::create_vm]:       struct.Library.html#method.create_vm
+/// [Library::get_created_vms]: struct.Library.html#method.get_created_vms
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Vm(NonNull<JavaVM>);
+
+// The invocation interface is thread safe, and JNIEnv pointers (which aren't) are only ever handed out per-thread
+unsafe impl Send for Vm {}
+unsafe impl Sync for Vm {}
+
+impl Vm {
+    /// Take ownership of a raw `JavaVM` pointer.  Returns `None` if `vm` is null.
+    ///
+    /// # Safety
+    ///
+    /// `vm` must be a valid Java VM.  Nothing else may destroy it while this handle lives, and if anything else still uses it
+    /// (another `Vm` for the same VM, a Java host, raw pointers), it must not be [destroy](#method.destroy)ed through this handle either.
+    pub unsafe fn from_raw(vm: *mut JavaVM) -> Option<Self> { NonNull::new(vm).map(Self) }
+
+    /// The raw `JavaVM` pointer.
+    pub fn as_raw(&self) -> *mut JavaVM { self.0.as_ptr() }
+
+    /// `AttachCurrentThread` - see [AttachGuard::attach](struct.AttachGuard.html#method.attach)
+    pub fn attach_current_thread(&self, name: Option<&str>) -> Result<AttachGuard<'_>, JniError> {
+        unsafe { AttachGuard::attach(self.as_raw(), name) }
+    }
+
+    /// `AttachCurrentThreadAsDaemon` - see [AttachGuard::attach_as_daemon](struct.AttachGuard.html#method.attach_as_daemon)
+    pub fn attach_current_thread_as_daemon(&self, name: Option<&str>) -> Result<AttachGuard<'_>, JniError> {
+        unsafe { AttachGuard::attach_as_daemon(self.as_raw(), name) }
+    }
+
+    /// `GetEnv` - the current thread's `JNIEnv` supporting JNI `version` (e.g. `JNI_VERSION_1_6`.)
+    ///
+    /// Fails with `JniError::DETACHED` if the current thread isn't attached, or `JniError::VERSION` if `version` isn't supported.
+    pub fn get_env(&self, version: jint) -> Result<*mut JNIEnv, JniError> {
+        let vm = self.as_raw();
+        let mut env = null_mut();
+        let r = unsafe { (**vm).GetEnv.unwrap()(vm, &mut env, version) };
+        if r == JNI_OK && !env.is_null() { Ok(env as *mut JNIEnv) } else { Err(JniError(if r == JNI_OK { JNI_ERR } else { r })) }
+    }
+
+    /// Call `f` with the current thread's `JNIEnv`, attaching (and detaching again afterwards) only if the thread isn't already attached.
+    pub fn with_env<R>(&self, f: impl FnOnce(*mut JNIEnv) -> R) -> Result<R, JniError> {
+        unsafe { with_env(self.as_raw(), f) }
+    }
+
+    /// `DetachCurrentThread` - detach the current thread, invalidating its `JNIEnv`.
+    ///
+    /// Prefer [AttachGuard](struct.AttachGuard.html)s, which only detach threads they attached themselves.  Java threads calling into native code can't be detached.
+    pub fn detach_current_thread(&self) -> Result<(), JniError> {
+        let vm = self.as_raw();
+        let r = unsafe { (**vm).DetachCurrentThread.unwrap()(vm) };
+        if r == JNI_OK { Ok(()) } else { Err(JniError(r)) }
+    }
+
+    /// `DestroyJavaVM` - wait for all non-daemon threads to exit, then unload the Java VM.
+    ///
+    /// Takes `self`, so no [AttachGuard](struct.AttachGuard.html)s of this VM can be alive.  Raw `JNIEnv`s and references obtained
+    /// through it are invalidated.
+    pub fn destroy(self) -> Result<(), JniError> {
+        let vm = self.as_raw();
+        let r = unsafe { (**vm).DestroyJavaVM.unwrap()(vm) };
+        if r == JNI_OK { Ok(()) } else { Err(JniError(r)) }
+    }
+}
+
+/// Attaches the current thread to a Java VM, and detaches it again when dropped.
+///
+/// If the thread is already attached (e.g. it's a Java thread calling into native code), that attachment is reused and left alone
+/// when the guard is dropped.  The guard can't be sent to other threads, as attachment is per-thread, and borrows the [Vm](struct.Vm.html)
+/// it was attached with, so the VM can't be destroyed while it lives.
+///
+/// ```no_run
+/// fn worker(vm: &jerk::jvm::Vm) {
+///     let guard = vm.attach_current_thread_as_daemon(Some("my-worker")).unwrap();
+///     let env = guard.env();
+///     // ...
+/// } // detached here
+/// ```
+pub struct AttachGuard<'vm> {
+    vm:     *mut JavaVM,
+    env:    *mut JNIEnv,
+    detach: bool,
+    _vm:    PhantomData<&'vm Vm>,
+}
+
+impl<'vm> AttachGuard<'vm> {
+    /// `AttachCurrentThread` - attach as a regular Java thread, which keeps the VM alive (`DestroyJavaVM` waits for it) until detached.
+    /// `name` is used for `Thread.getName()`.
+    ///
+    /// # Safety
+    ///
+    /// `vm` must be a valid Java VM that outlives the guard.
+    pub unsafe fn attach(vm: *mut JavaVM, name: Option<&str>) -> Result<Self, JniError> {
+        Self::attach_impl(vm, name, false)
+    }
+
+    /// `AttachCurrentThreadAsDaemon` - attach as a daemon Java thread, which doesn't keep the VM alive.
+    /// `name` is used for `Thread.getName()`.
+    ///
+    /// # Safety
+    ///
+    /// `vm` must be a valid Java VM that outlives the guard.
+    pub unsafe fn attach_as_daemon(vm: *mut JavaVM, name: Option<&str>) -> Result<Self, JniError> {
+        Self::attach_impl(vm, name, true)
+    }
+
+    unsafe fn attach_impl(vm: *mut JavaVM, name: Option<&str>, daemon: bool) -> Result<Self, JniError> {
+        if let Some(env) = current_env(vm) { return Ok(Self { vm, env, detach: false, _vm: PhantomData }); }
+
+        let name = name.map(|name| std::ffi::CString::new(name.replace('\0', "")).unwrap());
+        let mut args = JavaVMAttachArgs {
+            version:    JNI_VERSION_1_2,
+            name:       name.as_ref().map_or(null_mut(), |name| name.as_ptr() as *mut _),
+            group:      null_mut(),
+        };
+        let attach = if daemon { (**vm).AttachCurrentThreadAsDaemon.unwrap() } else { (**vm).AttachCurrentThread.unwrap() };
+        let mut env = null_mut();
+        let r = attach(vm, &mut env, &mut args as *mut JavaVMAttachArgs as *mut c_void);
+        if r == JNI_OK && !env.is_null() {
+            Ok(Self { vm, env: env as *mut JNIEnv, detach: true, _vm: PhantomData })
+        } else {
+            Err(JniError(if r == JNI_OK { JNI_ERR } else { r }))
+        }
+    }
+
+    /// The `JNIEnv` of the current thread.  Only valid while the guard (and the thread's attachment) lives.
+    pub fn env(&self) -> *mut JNIEnv { self.env }
+
+    /// The Java VM the current thread is attached to.
+    pub fn vm(&self) -> *mut JavaVM { self.vm }
+
+    /// `true` if this guard attached the thread, and will detach it when dropped.  `false` if it reused an existing attachment.
+    pub fn detaches_on_drop(&self) -> bool { self.detach }
+
+    /// `true` if the current thread is still attached to [vm](#method.vm) with the same [env](#method.env) (e.g. nobody else detached it.)
+    pub fn is_attached(&self) -> bool { unsafe { current_env(self.vm) == Some(self.env) } }
+}
+
+impl Drop for AttachGuard<'_> {
+    fn drop(&mut self) {
+        if self.detach && self.is_attached() {
+            unsafe { (**self.vm).DetachCurrentThread.unwrap()(self.vm) };
+        }
+    }
+}
+
+/// Call `f` with the current thread's `JNIEnv` for `vm`.  The thread is only attached (and detached again afterwards) if it isn't already.
+///
+/// # Safety
+///
+/// `vm` must be a valid Java VM.
+pub unsafe fn with_env<R>(vm: *mut JavaVM, f: impl FnOnce(*mut JNIEnv) -> R) -> Result<R, JniError> {
+    let guard = AttachGuard::attach(vm, None)?;
+    Ok(f(guard.env()))
+}
+
+/// `GetEnv` - the current thread's `JNIEnv` for `vm`, if it's attached.
+unsafe fn current_env(vm: *mut JavaVM) -> Option<*mut JNIEnv> {
+    let mut env = null_mut();
+    if (**vm).GetEnv.unwrap()(vm, &mut env, JNI_VERSION_1_2) == JNI_OK && !env.is_null() {
+        Some(env as *mut JNIEnv)
+    } else {
+        None
+    }
+}
@@ -1,14 +1,23 @@
 #![cfg_attr(feature = "nightly", feature(doc_cfg))] // https://doc.rust-lang.org/unstable-book/language-features/doc-cfg.html
 #![doc = include_str!("../Readme.md")]
 
+mod argfile;
+mod classfile;
 mod env;
 #[allow(dead_code)] mod jar;    // TODO: Make public after finalizing APIs?
-#[allow(dead_code)] mod java;   // TODO: Make public after finalizing APIs?
+pub mod java;
 #[allow(dead_code)] mod javac;  // TODO: Make public after finalizing APIs?
+#[allow(dead_code)] mod javadoc;// TODO: Make public after finalizing APIs?
+pub mod jdeps;
+pub mod jlink;
+mod jni;
 pub mod jvm;
 mod metabuild;
 pub mod paths;
+mod report;
 mod search;
+mod tool;
 #[macro_use] pub mod test;
+mod zip;
 
-pub use metabuild::metabuild;
+pub use metabuild::{metabuild, Metabuild};
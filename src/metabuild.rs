@@ -1,8 +1,9 @@
 use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{AsRef};
 use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 /// A cargo [metabuild] compatible entry point.
 ///
@@ -36,14 +37,113 @@ use std::path::{Path, PathBuf};
 /// jerk = "0.2"
 /// ```
 ///
+/// # Options
+///
+/// To customize the build, use [Metabuild] from [build.rs] instead:
+/// ```no_run
+/// fn main() {
+///     jerk::Metabuild {
+///         javadoc: true,
+///         ..jerk::Metabuild::default()
+///     }.run();
+/// }
+/// ```
+///
+/// # Multi-release jars
+///
+/// Sources inside a directory named `java9`, `java11`, etc. (`javaN` where `N >= 9`) are compiled separately with `--release N`,
+/// against the rest of the crate's classes, and packaged under `META-INF/versions/N/` with `Multi-Release: true` set in the manifest.
+/// When any such directory exists, the remaining sources are compiled with `--release 8`.
+///
+/// # Java tests
+///
+/// Compiled classes are scanned for `static void ...()` methods that are annotated with `@com.maulingmonkey.jerk.JerkTest`
+/// (provided automatically to sources that mention it) or named `test...`.  A `#[test]` is generated for each, nested in
+/// modules by package and class so `cargo test Adder` and the like filter as expected.  Include them from an integration test:
+/// ```ignore
+/// include!(env!("JERK_BUILD_TESTS"));
+/// ```
+///
+/// Methods annotated with JUnit 4's `@org.junit.Test` or JUnit 5's `@org.junit.jupiter.api.Test` get a `#[test]` that runs them
+/// through [run_junit_test!] instead.  JUnit's jars must be provided via [Metabuild::class_paths].
+///
+/// # Android
+///
+/// When targeting Android (`CARGO_CFG_TARGET_OS=android`), sources are compiled and documented as Java 8 against `android.jar` from the Android SDK
+/// instead of the JDK's class library, so APIs that don't exist on devices are rejected at build time.
+/// Set [Metabuild::android_api_level] to pick a specific `platforms/android-N`.  Multi-release directories are ignored.
+///
 /// [build.rs]:             https://doc.rust-lang.org/cargo/reference/build-scripts.html
 /// [metabuild]:            https://github.com/rust-lang/rfcs/blob/master/text/2196-metabuild.md
 pub fn metabuild() {
+    Metabuild::default().run()
+}
+
+/// Configurable version of [metabuild()].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Metabuild {
+    /// Generate HTML documentation for the crate's Java sources into `OUT_DIR/java/docs`.
+    /// The path is exposed to the crate as `env!("JERK_BUILD_JAVADOC")`.
+    pub javadoc:            bool,
+    /// Also package the documentation as `{package}-javadoc.jar` next to the crate's `.jar` (implies [javadoc](#structfield.javadoc).)
+    /// The path is exposed to the crate as `env!("JERK_BUILD_JAVADOC_JAR")`.
+    pub javadoc_jar:        bool,
+    /// Create a minimal runtime image (via `jdeps` + `jlink`, JDK 11+) containing only the JDK modules the crate's `.jar` needs, in `OUT_DIR/java/runtime`.
+    /// The path is exposed to the crate as `env!("JERK_BUILD_RUNTIME")`, and can be loaded with [jvm::Library::from_java_home].
+    /// Skipped with a warning on older JDKs, so use `option_env!("JERK_BUILD_RUNTIME")` if those must still build.
+    pub runtime_image:      bool,
+    /// The Android API level to compile against when targeting Android (`%ANDROID_SDK_ROOT%\platforms\android-{N}\android.jar`.)
+    /// Defaults to whichever platform [paths::platforms_android_nn] finds.
+    pub android_api_level:  Option<u32>,
+    /// Additional `.jar`s or directories of classes to compile against, and to add to the `java.class.path` of the Java VM that
+    /// [run_test!] and [run_junit_test!] use - e.g. a locally downloaded `junit-4.13.2.jar` + `hamcrest-core-1.3.jar`, or
+    /// `junit-platform-console-standalone-1.x.jar`.  Relative paths are relative to the crate's directory.
+    /// The full test class path, including the crate's own `.jar` and jerk's test support classes, is exposed to the crate as `env!("JERK_BUILD_CLASSPATH")`.
+    pub class_paths:        Vec<PathBuf>,
+    /// Compile through `javax.tools` in a Java VM loaded into the build script process, instead of spawning `bin/javac` per compile.
+    /// Saves JVM startup time (once per compile:  the main sources, each `javaN` directory, and tests), but the VM can't be unloaded,
+    /// so keeps its heap (often 100+ MB) until the build script exits.  Falls back to `bin/javac` if no in-process compiler is available.
+    /// Defaults to `true`.  Can be overridden with `JERK_JAVAC_IN_PROCESS=1`, or `JERK_JAVAC_IN_PROCESS=0` to always spawn `bin/javac`.
+    pub in_process_javac:   bool,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Default for Metabuild {
+    fn default() -> Self {
+        Self {
+            javadoc:            false,
+            javadoc_jar:        false,
+            runtime_image:      false,
+            android_api_level:  None,
+            class_paths:        Vec::new(),
+            in_process_javac:   true,
+            _non_exhaustive:    (),
+        }
+    }
+}
+
+impl Metabuild {
+    /// Build the crate's Java sources, as described by [metabuild()].
+    pub fn run(&self) {
+        build(self)
+    }
+}
+
+fn build(options: &Metabuild) {
     let java_home = paths::java_home().unwrap();
     println!("cargo:rerun-if-env-changed=JAVA_HOME");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
     println!("cargo:rustc-env=JAVA_HOME={}", java_home.display());
     env::set_var("JAVA_HOME", &java_home);
 
+    println!("cargo:rerun-if-env-changed=JERK_JAVAC_IN_PROCESS");
+    let in_process = match env::var("JERK_JAVAC_IN_PROCESS").as_deref() {
+        Ok("1") => true,
+        Ok("0") => false,
+        _       => options.in_process_javac,
+    };
+
     let profile         = env::var("PROFILE").expect("${PROFILE} is not set or is invalid Unicode");
     let package_name    = env::var("CARGO_PKG_NAME").expect("${CARGO_PKG_NAME} is not set or is invalid Unicode");
     let out_dir         = env::var_path("OUT_DIR").expect("${OUT_DIR} is not set or is invalid Unicode");
@@ -56,6 +156,11 @@ pub fn metabuild() {
 
     let mut files = Vec::new();
     find_java_srcs(Path::new("."), &mut files).unwrap_or_else(|err| panic!("Failed to enumerate/read Java source code: {}", err));
+    let mut versioned_files = BTreeMap::<u32, Vec<PathBuf>>::new();
+    files.retain(|file| match multi_release_version(file) {
+        Some(version)   => { versioned_files.entry(version).or_default().push(file.clone()); false },
+        None            => true,
+    });
     // This is a very limited and kinda poor heuristic for detecting entry points.  https://github.com/MaulingMonkey/jerk/issues/18
     let entry_point = if files.iter().find(|p| p.file_name() == Some(std::ffi::OsStr::new("Main.java"))).is_some() { Some(String::from("Main")) } else { None };
 
@@ -84,30 +189,522 @@ pub fn metabuild() {
     }.join(format!("{}.jar", package_name));
 
     let _ = fs::create_dir_all(&out_java);
+    let _ = fs::remove_dir_all(&out_classes); // don't package or discover tests in classes of deleted sources
     let _ = fs::create_dir(&out_classes);
     let _ = fs::create_dir(&out_sources);
     let _ = fs::create_dir(&out_headers);
     let _ = fs::create_dir(&out_jars);
 
+    let android_jar = if env::var("CARGO_CFG_TARGET_OS").map_or(false, |os| os == "android") {
+        println!("cargo:rerun-if-env-changed=ANDROID_HOME");
+        println!("cargo:rerun-if-env-changed=ANDROID_SDK_ROOT");
+        if !versioned_files.is_empty() {
+            println!("cargo:warning=Android doesn't support multi-release jars, ignoring javaN/ sources");
+            versioned_files.clear();
+        }
+        Some(find_android_jar(options.android_api_level).unwrap_or_else(|err| panic!("Failed to find android.jar: {}", err)))
+    } else {
+        None
+    };
+
+    let class_paths = options.class_paths.iter().map(|path| env::current_dir().unwrap().join(path)).collect::<Vec<_>>();
+    for path in class_paths.iter() { println!("cargo:rerun-if-changed={}", path.display()); }
+
+    let javadoc_files = if options.javadoc || options.javadoc_jar { files.clone() } else { Vec::new() };
+
+    let sources = files.iter().filter_map(|file| fs::read_to_string(file).ok()).collect::<Vec<_>>();
+    if sources.iter().any(|src| src.contains("JerkTest")) {
+        let dir = out_java.join("jerk-source").join("com").join("maulingmonkey").join("jerk");
+        fs::create_dir_all(&dir).unwrap_or_else(|err| panic!("Failed to create {}: {}", dir.display(), err));
+        let path = dir.join("JerkTest.java");
+        if fs::read_to_string(&path).ok().as_deref() != Some(JERK_TEST_JAVA) { fs::write(&path, JERK_TEST_JAVA).unwrap(); }
+        files.push(path);
+    }
+
     javac::Compile {
         java_home: Some(java_home.clone()),
         debug_info,
+        // `--release` can't be combined with `-bootclasspath`, so Android sources are compiled as Java 8 with `-source`/`-target` instead
+        source: android_jar.as_ref().map(|_| ANDROID_JAVA_VERSION),
+        target: android_jar.as_ref().map(|_| ANDROID_JAVA_VERSION),
+        boot_class_paths: android_jar.iter().cloned().collect(),
+        class_paths: class_paths.clone(),
+        release: if versioned_files.is_empty() { None } else { Some(8) },
         out_classes: Some(out_classes.clone()),
-        out_sources: Some(out_sources),
-        out_headers: Some(out_headers),
+        out_sources: Some(out_sources.clone()),
+        out_headers: Some(out_headers.clone()),
         files,
+        in_process,
         ..javac::Compile::default()
     }.exec().unwrap();
 
+    let out_tests = out_java.join("tests.rs");
+    let tests = find_java_tests(&out_classes).unwrap_or_else(|err| panic!("Failed to scan compiled classes for tests: {}", err));
+    fs::write(&out_tests, generate_java_tests(&tests)).unwrap();
+    println!("cargo:rustc-env=JERK_BUILD_TESTS={}", out_tests.display());
+
+    for (version, files) in versioned_files.iter() {
+        // Compiled straight into the jar's directory layout, so the single `-C out_classes .` below picks them up
+        let out_versioned = out_classes.join("META-INF").join("versions").join(version.to_string());
+        fs::create_dir_all(&out_versioned).unwrap_or_else(|err| panic!("Failed to create {}: {}", out_versioned.display(), err));
+        javac::Compile {
+            java_home: Some(java_home.clone()),
+            debug_info,
+            release: Some(*version),
+            class_paths: std::iter::once(out_classes.clone()).chain(class_paths.iter().cloned()).collect(),
+            out_classes: Some(out_versioned),
+            out_sources: Some(out_sources.clone()),
+            out_headers: Some(out_headers.clone()),
+            files: files.clone(),
+            in_process,
+            ..javac::Compile::default()
+        }.exec().unwrap();
+    }
+
+    let manifest = jar::Manifest {
+        implementation_version: env::var("CARGO_PKG_VERSION").ok(),
+        multi_release:          !versioned_files.is_empty(),
+        ..jar::Manifest::default()
+    };
+
     jar::Archive {
         entry_point,
+        manifest:       Some(&manifest),
         java_home:      Some(java_home.as_ref()),
         jar_file:       Some(out_jar.as_ref()),
         files:          &[(out_classes.as_ref(), &[".".as_ref()][..])][..],
         ..jar::Archive::default()
-    }.create().unwrap();
+    }.write().unwrap();
 
     println!("cargo:rustc-env=JERK_BUILD_JAR={}", out_jar.display());
+
+    // Test support classes for jerk::test, kept out of the crate's .jar
+    let out_test_classes = out_java.join("jerk-test-classes");
+    let test_sources_dir = out_java.join("jerk-test-source").join("com").join("maulingmonkey").join("jerk");
+    let test_classes_dir = out_test_classes.join("com").join("maulingmonkey").join("jerk");
+    let test_sources = [("IsolatedClassLoader", JERK_ISOLATED_CLASS_LOADER_JAVA), ("JUnit", JERK_JUNIT_JAVA), ("TestOutputStream", JERK_TEST_OUTPUT_STREAM_JAVA)];
+    let stale = test_sources.iter().any(|(class, source)| {
+        fs::read_to_string(test_sources_dir.join(format!("{}.java", class))).ok().as_deref() != Some(*source) ||
+        !test_classes_dir.join(format!("{}.class", class)).exists()
+    });
+    if stale {
+        fs::create_dir_all(&test_sources_dir).unwrap_or_else(|err| panic!("Failed to create {}: {}", test_sources_dir.display(), err));
+        fs::create_dir_all(&out_test_classes).unwrap_or_else(|err| panic!("Failed to create {}: {}", out_test_classes.display(), err));
+        let mut files = Vec::new();
+        for (class, source) in test_sources.iter() {
+            let path = test_sources_dir.join(format!("{}.java", class));
+            fs::write(&path, source).unwrap();
+            files.push(path);
+        }
+        javac::Compile {
+            java_home: Some(java_home.clone()),
+            debug_info,
+            out_classes: Some(out_test_classes.clone()),
+            files,
+            in_process,
+            ..javac::Compile::default()
+        }.exec().unwrap();
+    }
+
+    let classpath = env::join_paths(std::iter::once(&out_jar).chain(class_paths.iter()).chain(std::iter::once(&out_test_classes))).expect("class_paths contain characters that can't be used in a class path");
+    println!("cargo:rustc-env=JERK_BUILD_CLASSPATH={}", classpath.to_string_lossy());
+
+    if options.runtime_image && paths::java_feature_version(&java_home).map_or(false, |version| version < 11) {
+        println!("cargo:warning=Metabuild::runtime_image requires JDK 11+ (for jlink and jdeps --print-module-deps), skipping with JAVA_HOME={}", java_home.display());
+    } else if options.runtime_image {
+        let out_runtime = out_java.join("runtime");
+        jlink::minimal_runtime(Some(java_home.as_ref()), &[out_jar.as_ref()], &out_runtime).unwrap();
+        println!("cargo:rustc-env=JERK_BUILD_RUNTIME={}", out_runtime.display());
+    }
+
+    if options.javadoc || options.javadoc_jar {
+        let out_docs = out_java.join("docs");
+        let _ = fs::remove_dir_all(&out_docs); // don't keep docs for deleted classes
+        let _ = fs::create_dir(&out_docs);
+        javadoc::Document {
+            java_home:  Some(java_home.clone()),
+            quiet:      true,
+            source:     android_jar.as_ref().map(|_| ANDROID_JAVA_VERSION),
+            boot_class_paths: android_jar.iter().cloned().collect(),
+            release:    if versioned_files.is_empty() { None } else { Some(8) },
+            class_paths: std::iter::once(out_classes.clone()).chain(class_paths.iter().cloned()).collect(),
+            doc_title:  Some(package_name.clone()),
+            out_dir:    Some(out_docs.clone()),
+            files:      javadoc_files,
+            ..javadoc::Document::default()
+        }.exec().unwrap();
+        println!("cargo:rustc-env=JERK_BUILD_JAVADOC={}", out_docs.display());
+
+        if options.javadoc_jar {
+            let out_javadoc_jar = out_jar.with_file_name(format!("{}-javadoc.jar", package_name));
+            jar::Archive {
+                java_home:  Some(java_home.as_ref()),
+                jar_file:   Some(out_javadoc_jar.as_ref()),
+                files:      &[(out_docs.as_ref(), &[".".as_ref()][..])][..],
+                ..jar::Archive::default()
+            }.write().unwrap();
+            println!("cargo:rustc-env=JERK_BUILD_JAVADOC_JAR={}", out_javadoc_jar.display());
+        }
+    }
+}
+
+/// Compiled alongside the crate's sources if they reference it
+const JERK_TEST_JAVA : &str = r#"package com.maulingmonkey.jerk;
+
+import java.lang.annotation.*;
+
+/** Marks a static, void, parameterless method as a test to generate a Rust #[test] for. */
+@Retention(RetentionPolicy.RUNTIME)
+@Target(ElementType.METHOD)
+public @interface JerkTest {}
+"#;
+
+/// How a discovered Java test is run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum JavaTestKind {
+    /// `run_test!` - a `static void ...()` method annotated with `@JerkTest` or named `test...`
+    Static,
+    /// `run_junit_test!` - a method annotated with JUnit 4's `@org.junit.Test` or JUnit 5's `@org.junit.jupiter.api.Test`
+    JUnit,
+}
+
+/// Find test methods in `out_classes`, as `(package, class, method, kind)`.
+fn find_java_tests(out_classes: &Path) -> io::Result<BTreeSet<(String, String, String, JavaTestKind)>> {
+    let mut tests = BTreeSet::new();
+    let mut dirs = vec![out_classes.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path.file_name() != Some("META-INF".as_ref()) { dirs.push(path); }
+                continue;
+            } else if path.extension() != Some("class".as_ref()) {
+                continue;
+            }
+
+            let class = classfile::ClassFile::read(&path)?;
+            let (package, class_name) = match class.name.rfind('/') {
+                Some(slash) => (class.name[..slash].replace('/', "."), &class.name[slash+1..]),
+                None        => (String::new(), &class.name[..]),
+            };
+            for method in class.methods.iter() {
+                let is_static = method.access_flags & classfile::ACC_STATIC != 0;
+                let is_generated = method.access_flags & (classfile::ACC_BRIDGE | classfile::ACC_SYNTHETIC) != 0;
+                let is_annotated = |annotation: &str| method.annotations.iter().any(|a| a == annotation);
+                let is_test = is_annotated("Lcom/maulingmonkey/jerk/JerkTest;") || method.name.starts_with("test");
+                let is_junit = is_annotated("Lorg/junit/Test;") || is_annotated("Lorg/junit/jupiter/api/Test;");
+                if is_generated {
+                    continue;
+                } else if is_junit {
+                    tests.insert((package.clone(), class_name.to_string(), method.name.clone(), JavaTestKind::JUnit));
+                } else if is_static && is_test && method.descriptor == "()V" {
+                    tests.insert((package.clone(), class_name.to_string(), method.name.clone(), JavaTestKind::Static));
+                }
+            }
+        }
+    }
+    Ok(tests)
+}
+
+/// Generate `#[test]`s for `tests`, nested in modules by package and class so e.g. `cargo test com::example::Adder` works.
+fn generate_java_tests(tests: &BTreeSet<(String, String, String, JavaTestKind)>) -> String {
+    // Sort by path segments rather than dotted package name, so each module's contents are contiguous (`a.b$` sorts between `a.b` and `a.b.c`)
+    let mut tests = tests.iter().map(|(package, class, method, kind)| {
+        let path = package.split('.').filter(|s| !s.is_empty()).chain(std::iter::once(&class[..])).collect::<Vec<_>>();
+        (path, package, class, method, kind)
+    }).collect::<Vec<_>>();
+    tests.sort();
+
+    let mut out = String::from("// @generated by jerk::metabuild from the crate's compiled Java classes.  Use via `include!(env!(\"JERK_BUILD_TESTS\"));`\n");
+    let mut idents = RustIdents::default();
+    let mut open : Vec<&str> = Vec::new();
+    let mut open_idents : Vec<String> = Vec::new();
+    for (path, package, class, method, kind) in tests.into_iter() {
+        let common = open.iter().zip(path.iter()).take_while(|(a, b)| a == b).count();
+        while open.len() > common {
+            open.pop();
+            open_idents.pop();
+            out.push_str(&format!("{}}}\n", "    ".repeat(open.len())));
+        }
+        while open.len() < path.len() {
+            let ident = idents.get(&format!("mod {}", open_idents.join("::")), path[open.len()]);
+            out.push_str(&format!("{}#[allow(non_snake_case)] mod {} {{\n", "    ".repeat(open.len()), ident));
+            open.push(path[open.len()]);
+            open_idents.push(ident);
+        }
+        let run = match kind { JavaTestKind::Static => "run_test", JavaTestKind::JUnit => "run_junit_test" };
+        let ident = idents.get(&format!("fn {}", open_idents.join("::")), method);
+        out.push_str(&format!("{}#[test] fn {}() {{ ::jerk::{}!({:?}, {:?}, {:?}); }}\n", "    ".repeat(open.len()), ident, run, package, class, method));
+    }
+    while !open.is_empty() {
+        open.pop();
+        out.push_str(&format!("{}}}\n", "    ".repeat(open.len())));
+    }
+    out
+}
+
+/// Rust identifiers for Java names, unique within each scope: Java names that [rust_ident] maps to the same identifier
+/// (e.g. `Foo$Bar` and `Foo_Bar`) get numeric suffixes (`Foo_Bar`, `Foo_Bar_2`) in sorted order.
+#[derive(Default)]
+struct RustIdents {
+    assigned:   BTreeMap<(String, String), String>, // (scope, java name) -> rust ident
+    taken:      BTreeSet<(String, String)>,         // (scope, rust ident)
+}
+
+impl RustIdents {
+    fn get(&mut self, scope: &str, java: &str) -> String {
+        let key = (scope.to_string(), java.to_string());
+        if let Some(ident) = self.assigned.get(&key) { return ident.clone(); }
+
+        let base = rust_ident(java);
+        let mut ident = base.clone();
+        let mut suffix = 2;
+        while !self.taken.insert((scope.to_string(), ident.clone())) {
+            ident = format!("{}_{}", base.trim_start_matches("r#"), suffix);
+            suffix += 1;
+        }
+        self.assigned.insert(key, ident.clone());
+        ident
+    }
+}
+
+/// Convert a Java identifier into a Rust one (`$` and other non-Rust characters become `_`, keywords become raw identifiers.)
+fn rust_ident(java: &str) -> String {
+    const KEYWORDS : &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+        "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use",
+        "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+    ];
+    let mut ident = java.chars().map(|ch| if ch.is_ascii_alphanumeric() || ch == '_' { ch } else { '_' }).collect::<String>();
+    if ident.is_empty() || ident.starts_with(|ch: char| ch.is_ascii_digit()) { ident.insert(0, '_'); }
+    match ident.as_str() {
+        "crate" | "self" | "super" | "Self" | "_"   => { ident.push('_'); ident },
+        kw if KEYWORDS.contains(&kw)                => format!("r#{}", ident),
+        _                                           => ident,
+    }
+}
+
+/// Compiled into jerk's test support classes, out of the crate's `.jar`.  Uses reflection so it builds and loads whether JUnit 4,
+/// the JUnit Platform (5), or both are on the class path.  Throws the test's failure, so [run_junit_test!] reports it like any other exception.
+const JERK_JUNIT_JAVA : &str = r#"package com.maulingmonkey.jerk;
+
+import java.lang.reflect.*;
+import java.util.List;
+
+/** Runs a single JUnit test method for jerk::run_junit_test! */
+public final class JUnit {
+    private JUnit() {}
+
+    public static void run(String className, String methodName) throws Throwable {
+        ClassLoader loader = JUnit.class.getClassLoader();
+        if (Thread.currentThread().getContextClassLoader() == null) Thread.currentThread().setContextClassLoader(loader); // JUnit Platform finds engines through it
+        Class<?> testClass = Class.forName(className, true, loader);
+        boolean jupiter = false;
+        for (Method method : testClass.getMethods()) {
+            if (!method.getName().equals(methodName)) continue;
+            for (java.lang.annotation.Annotation a : method.getAnnotations()) {
+                if (a.annotationType().getName().startsWith("org.junit.jupiter.")) jupiter = true;
+            }
+        }
+        if (!jupiter && load(loader, "org.junit.runner.JUnitCore") != null) {
+            runJUnit4(loader, testClass, methodName);
+        } else if (load(loader, "org.junit.platform.launcher.core.LauncherFactory") != null) {
+            runPlatform(loader, testClass, methodName);
+        } else {
+            throw new ClassNotFoundException("Neither JUnit 4 (org.junit.runner.JUnitCore) nor the JUnit Platform (org.junit.platform.launcher) was found on the class path, see jerk::Metabuild::class_paths");
+        }
+    }
+
+    private static void runJUnit4(ClassLoader loader, Class<?> testClass, String methodName) throws Throwable {
+        Class<?> requestClass = load(loader, "org.junit.runner.Request");
+        Class<?> resultClass  = load(loader, "org.junit.runner.Result");
+        Class<?> failureClass = load(loader, "org.junit.runner.notification.Failure");
+        Class<?> coreClass    = load(loader, "org.junit.runner.JUnitCore");
+        Object request = invoke(requestClass.getMethod("method", Class.class, String.class), null, testClass, methodName);
+        Object result  = invoke(coreClass.getMethod("run", requestClass), coreClass.getConstructor().newInstance(), request);
+        List<?> failures = (List<?>) invoke(resultClass.getMethod("getFailures"), result);
+        if (!failures.isEmpty()) throw (Throwable) invoke(failureClass.getMethod("getException"), failures.get(0));
+        int run     = (Integer) invoke(resultClass.getMethod("getRunCount"), result);
+        int ignored = (Integer) invoke(resultClass.getMethod("getIgnoreCount"), result);
+        if (ignored > 0) System.out.println("jerk: JUnit ignored " + testClass.getName() + "." + methodName);
+        else if (run == 0) throw new AssertionError("JUnit found no test " + testClass.getName() + "." + methodName);
+    }
+
+    private static void runPlatform(ClassLoader loader, Class<?> testClass, String methodName) throws Throwable {
+        Class<?> selectorClass  = load(loader, "org.junit.platform.engine.DiscoverySelector");
+        Class<?> selectorsClass = load(loader, "org.junit.platform.engine.discovery.DiscoverySelectors");
+        Class<?> builderClass   = load(loader, "org.junit.platform.launcher.core.LauncherDiscoveryRequestBuilder");
+        Class<?> requestClass   = load(loader, "org.junit.platform.launcher.LauncherDiscoveryRequest");
+        Class<?> launcherClass  = load(loader, "org.junit.platform.launcher.Launcher");
+        Class<?> factoryClass   = load(loader, "org.junit.platform.launcher.core.LauncherFactory");
+        Class<?> listenerClass  = load(loader, "org.junit.platform.launcher.TestExecutionListener");
+        Class<?> summaryClass   = load(loader, "org.junit.platform.launcher.listeners.TestExecutionSummary");
+        Class<?> failureClass   = load(loader, "org.junit.platform.launcher.listeners.TestExecutionSummary$Failure");
+        Class<?> summaryGeneratingListenerClass = load(loader, "org.junit.platform.launcher.listeners.SummaryGeneratingListener");
+
+        Object selectors = Array.newInstance(selectorClass, 1);
+        Array.set(selectors, 0, invoke(selectorsClass.getMethod("selectMethod", Class.class, String.class), null, testClass, methodName));
+        Object builder   = invoke(builderClass.getMethod("request"), null);
+        invoke(builderClass.getMethod("selectors", selectors.getClass()), builder, selectors);
+        Object request   = invoke(builderClass.getMethod("build"), builder);
+
+        Object listener  = summaryGeneratingListenerClass.getConstructor().newInstance();
+        Object listeners = Array.newInstance(listenerClass, 1);
+        Array.set(listeners, 0, listener);
+        Object launcher  = invoke(factoryClass.getMethod("create"), null);
+        invoke(launcherClass.getMethod("execute", requestClass, listeners.getClass()), launcher, request, listeners);
+
+        Object summary = invoke(summaryGeneratingListenerClass.getMethod("getSummary"), listener);
+        List<?> failures = (List<?>) invoke(summaryClass.getMethod("getFailures"), summary);
+        if (!failures.isEmpty()) throw (Throwable) invoke(failureClass.getMethod("getException"), failures.get(0));
+        long found   = (Long) invoke(summaryClass.getMethod("getTestsFoundCount"), summary);
+        long skipped = (Long) invoke(summaryClass.getMethod("getTestsSkippedCount"), summary);
+        long aborted = (Long) invoke(summaryClass.getMethod("getTestsAbortedCount"), summary);
+        if (skipped + aborted > 0) System.out.println("jerk: JUnit skipped " + testClass.getName() + "." + methodName);
+        else if (found == 0) throw new AssertionError("JUnit found no test " + testClass.getName() + "." + methodName + " (is the matching test engine on the class path?)");
+    }
+
+    private static Class<?> load(ClassLoader loader, String name) {
+        try { return Class.forName(name, false, loader); } catch (ClassNotFoundException e) { return null; }
+    }
+
+    private static Object invoke(Method method, Object self, Object... args) throws Throwable {
+        try { return method.invoke(self, args); } catch (InvocationTargetException e) { throw e.getCause(); }
+    }
+}
+"#;
+
+/// Compiled into a separate directory on `JERK_BUILD_CLASSPATH` for `jerk::test::TestClassLoader::{PerTest, PerClass}`.
+const JERK_ISOLATED_CLASS_LOADER_JAVA : &str = r#"package com.maulingmonkey.jerk;
+
+import java.io.File;
+import java.io.IOException;
+import java.net.URL;
+import java.net.URLClassLoader;
+import java.nio.file.*;
+
+/** A fresh class loader for jerk::run_test!, so tests see freshly initialized static state. */
+public final class IsolatedClassLoader extends URLClassLoader {
+    private Path links;
+
+    public IsolatedClassLoader(URL[] urls) {
+        // Skip the system class loader, which would otherwise supply its own copies of the same classes
+        super(urls, ClassLoader.getSystemClassLoader().getParent());
+    }
+
+    /**
+     * The JDK refuses to load a native library into more than one class loader, so load a hard link to it instead.
+     * On Linux and macOS, that's still the same library, so native methods share state with the rest of the test process.
+     */
+    @Override protected synchronized String findLibrary(String name) {
+        String file = System.mapLibraryName(name);
+        for (String dir : System.getProperty("java.library.path", "").split(File.pathSeparator)) {
+            Path library = Paths.get(dir.isEmpty() ? "." : dir, file).toAbsolutePath();
+            if (!Files.isRegularFile(library)) continue;
+            try {
+                if (links == null) {
+                    // Libraries loaded from these can't be deleted on Windows until the process exits, so clean up after earlier runs instead
+                    if (File.separatorChar == '\\') deleteStaleLinks(library.getParent());
+                    links = Files.createTempDirectory(library.getParent(), ".jerk-test-"); // hard links can't cross filesystems
+                }
+                Path link = links.resolve(file);
+                if (!Files.exists(link)) Files.createLink(link, library);
+                return link.toString();
+            } catch (IOException | UnsupportedOperationException | SecurityException e) {
+                return null;
+            }
+        }
+        return null;
+    }
+
+    /** Called by jerk::test for every IsolatedClassLoader, at the latest when the test process exits. */
+    @Override public synchronized void close() throws IOException {
+        super.close();
+        if (links == null) return;
+        deleteLinks(links);
+        links = null;
+    }
+
+    private static void deleteStaleLinks(Path dir) {
+        long cutoff = System.currentTimeMillis() - 60 * 1000; // give concurrent test processes time to load their links
+        try (DirectoryStream<Path> stale = Files.newDirectoryStream(dir, ".jerk-test-*")) {
+            for (Path links : stale) if (Files.getLastModifiedTime(links).toMillis() < cutoff) deleteLinks(links);
+        } catch (IOException | DirectoryIteratorException e) {
+            // best effort
+        }
+    }
+
+    private static void deleteLinks(Path links) {
+        try {
+            try (DirectoryStream<Path> entries = Files.newDirectoryStream(links)) {
+                for (Path entry : entries) Files.deleteIfExists(entry);
+            }
+            Files.deleteIfExists(links);
+        } catch (IOException | DirectoryIteratorException e) {
+            // e.g. Windows, where loaded libraries can't be deleted
+        }
+    }
+}
+"#;
+
+/// Compiled into a separate directory on `JERK_BUILD_CLASSPATH` for `jerk::test::TestVmOptions::capture_output`.
+const JERK_TEST_OUTPUT_STREAM_JAVA : &str = r#"package com.maulingmonkey.jerk;
+
+import java.io.OutputStream;
+import java.io.PrintStream;
+import java.io.UnsupportedEncodingException;
+
+/** Forwards System.out and System.err to Rust's print! and eprint!, so libtest captures Java output per test. */
+public final class TestOutputStream extends OutputStream {
+    private final int fd;
+
+    private TestOutputStream(int fd) {
+        this.fd = fd;
+    }
+
+    /** Called by jerk::test after registering {@link #write(int, byte[], int, int)}. */
+    public static void install() throws UnsupportedEncodingException {
+        System.setOut(new PrintStream(new TestOutputStream(1), true, "UTF-8"));
+        System.setErr(new PrintStream(new TestOutputStream(2), true, "UTF-8"));
+    }
+
+    @Override public void write(int b) {
+        write(fd, new byte[] { (byte) b }, 0, 1);
+    }
+
+    @Override public void write(byte[] b, int off, int len) {
+        if (off < 0 || len < 0 || len > b.length - off) throw new IndexOutOfBoundsException();
+        write(fd, b, off, len);
+    }
+
+    private static native void write(int fd, byte[] b, int off, int len);
+}
+"#;
+
+/// The Java language version Android sources are compiled for.  D8 desugars Java 8 features for every API level.
+const ANDROID_JAVA_VERSION : u32 = 8;
+
+/// `%ANDROID_SDK_ROOT%\platforms\android-{api_level}\android.jar`, or any installed platform's `android.jar` if `api_level` is `None`.
+fn find_android_jar(api_level: Option<u32>) -> io::Result<PathBuf> {
+    let android_sdk_root = paths::android_sdk_root()?;
+    let platform = match api_level {
+        Some(api_level) => android_sdk_root.join("platforms").join(format!("android-{}", api_level)),
+        None            => paths::platforms_android_nn(&android_sdk_root)?,
+    };
+    let android_jar = platform.join("android.jar");
+    if android_jar.exists() {
+        Ok(android_jar)
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", android_jar.display())))
+    }
+}
+
+/// `Some(N)` if `path` is inside a `javaN` directory (where `N >= 9`)
+fn multi_release_version(path: &Path) -> Option<u32> {
+    path.parent()?.components().rev().find_map(|c| match c {
+        Component::Normal(dir) => dir.to_str()?.strip_prefix("java")?.parse().ok().filter(|&n| n >= 9),
+        _other => None,
+    })
 }
 
 fn find_java_srcs(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
@@ -120,6 +717,7 @@ fn find_java_srcs(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
         const DOT_JAVA : &'static str = ".java";
 
         if path.is_dir() {
+            if path.join("CACHEDIR.TAG").exists() { continue; } // e.g. cargo's `target` dir, which may contain other crates' generated sources
             find_java_srcs(&path, files)?;
         } else if name_lossy.get(name_lossy.len().saturating_sub(DOT_JAVA.len())..).map(|ext| ext.eq_ignore_ascii_case(DOT_JAVA)).unwrap_or(false) {
             println!("cargo:rerun-if-changed={}", path.display()); // rerun if source file changed
@@ -128,3 +726,157 @@ fn find_java_srcs(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// Just enough of `java.lang` (and `android.app`) for javac and javadoc to work against, instead of a real `android.jar`
+    const ANDROID_JAR_STUBS : &[(&str, &str)] = &[
+        ("java/lang/Object.java",           "package java.lang; public class Object {}"),
+        ("java/lang/String.java",           "package java.lang; public final class String {}"),
+        ("java/lang/Class.java",            "package java.lang; public final class Class<T> {}"),
+        ("java/lang/Throwable.java",        "package java.lang; public class Throwable {}"),
+        ("java/lang/Exception.java",        "package java.lang; public class Exception extends Throwable {}"),
+        ("java/lang/RuntimeException.java", "package java.lang; public class RuntimeException extends Exception {}"),
+        ("java/lang/Error.java",            "package java.lang; public class Error extends Throwable {}"),
+        ("android/app/Activity.java",       "package android.app; public class Activity { protected void onCreate() {} }"),
+    ];
+
+    const USES_ANDROID : &str = r#"package com.maulingmonkey.jerk.android_fixture;
+
+import android.app.Activity;
+
+/** Only compiles (and documents) against android.jar */
+public class HelloActivity extends Activity {
+    /** Overrides {@link Activity#onCreate()} */
+    protected void onCreate() { super.onCreate(); }
+}
+"#;
+
+    const USES_JDK_ONLY : &str = r#"package com.maulingmonkey.jerk.android_fixture;
+
+/** java.util doesn't exist in the stub android.jar, so this must fail to compile when targeting Android */
+public class HelloActivity {
+    public static Object list() { return new java.util.ArrayList<Object>(); }
+}
+"#;
+
+    /// Build a crate containing `HelloActivity.java` the way cargo would when targeting Android, against a stub `android.jar`.
+    /// Runs [android_metabuild] in a child process, since `Metabuild` reads its environment and current directory.
+    fn build_android_fixture(name: &str, source: &str) -> (std::process::Output, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("jerk-android-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+
+        let stub_sources = dir.join("stub-sources");
+        let stub_classes = dir.join("stub-classes");
+        fs::create_dir_all(&stub_classes).unwrap();
+        let mut files = Vec::new();
+        for (path, source) in ANDROID_JAR_STUBS.iter() {
+            let path = stub_sources.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, source).unwrap();
+            files.push(path);
+        }
+        javac::Compile {
+            java_home:          Some(paths::java_home().unwrap()),
+            // java.lang itself can only be compiled in pre-module mode
+            source:             Some(ANDROID_JAVA_VERSION),
+            target:             Some(ANDROID_JAVA_VERSION),
+            boot_class_paths:   vec![stub_classes.clone()],
+            out_classes:        Some(stub_classes.clone()),
+            files,
+            ..javac::Compile::default()
+        }.exec().unwrap();
+
+        let sdk = dir.join("sdk");
+        let platform = sdk.join("platforms").join("android-28");
+        fs::create_dir_all(&platform).unwrap();
+        jar::Archive {
+            jar_file:   Some(&platform.join("android.jar")),
+            files:      &[(&stub_classes, &[Path::new(".")])],
+            ..jar::Archive::default()
+        }.write().unwrap();
+
+        let krate = dir.join("crate");
+        fs::create_dir_all(krate.join("src")).unwrap();
+        fs::write(krate.join("src").join("HelloActivity.java"), source).unwrap();
+
+        let out_dir = dir.join("out");
+        fs::create_dir_all(&out_dir).unwrap();
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args(["metabuild::tests::android_metabuild", "--exact", "--ignored", "--nocapture", "--test-threads=1"])
+            .current_dir(&krate)
+            .env("JERK_TEST_ANDROID_METABUILD", "1")
+            .env("CARGO_CFG_TARGET_OS", "android")
+            .env("CARGO_PKG_NAME", "android-fixture")
+            .env("PROFILE", "debug")
+            .env("OUT_DIR", &out_dir)
+            .env("ANDROID_SDK_ROOT", &sdk)
+            .env_remove("ANDROID_HOME")
+            .env_remove("JERK_JAVAC_IN_PROCESS")
+            .output().unwrap();
+        (output, out_dir)
+    }
+
+    #[test] #[ignore = "run by build_android_fixture"] fn android_metabuild() {
+        if std::env::var_os("JERK_TEST_ANDROID_METABUILD").is_none() { return; }
+        Metabuild { javadoc: true, android_api_level: Some(28), ..Metabuild::default() }.run();
+    }
+
+    #[test] fn android() {
+        let (output, out_dir) = build_android_fixture("uses-android", USES_ANDROID);
+        assert!(output.status.success(), "{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+
+        let jar = zip::Reader::open(&out_dir.join("java").join("jars").join("android-fixture.jar")).unwrap();
+        let class = jar.data(jar.find("com/maulingmonkey/jerk/android_fixture/HelloActivity.class").unwrap()).unwrap();
+        assert_eq!(class[6..8], [0, 52]); // Java 8 class file major version
+        assert!(out_dir.join("java/docs/com/maulingmonkey/jerk/android_fixture/HelloActivity.html").exists());
+    }
+
+    #[test] fn android_rejects_jdk_only_apis() {
+        let (output, _out_dir) = build_android_fixture("uses-jdk-only", USES_JDK_ONLY);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{}", stderr);
+        assert!(stderr.contains("error: package java.util does not exist"), "{}", stderr);
+    }
+
+    #[test] fn rust_idents() {
+        assert_eq!(rust_ident("Foo$Bar"),   "Foo_Bar");
+        assert_eq!(rust_ident("1st"),       "_1st");
+        assert_eq!(rust_ident("fn"),        "r#fn");
+        assert_eq!(rust_ident("self"),      "self_");
+        assert_eq!(rust_ident("_"),         "__");
+
+        let mut idents = RustIdents::default();
+        assert_eq!(idents.get("fn a", "Foo$Bar"),   "Foo_Bar");
+        assert_eq!(idents.get("fn a", "Foo_Bar"),   "Foo_Bar_2");
+        assert_eq!(idents.get("fn a", "Foo_Bar_2"), "Foo_Bar_2_2");
+        assert_eq!(idents.get("fn a", "Foo$Bar"),   "Foo_Bar");     // the same Java name gets the same identifier again
+        assert_eq!(idents.get("fn b", "Foo_Bar"),   "Foo_Bar");     // scopes are independent
+        assert_eq!(idents.get("fn a", "fn"),        "r#fn");
+        assert_eq!(idents.get("fn a", "fn$"),       "fn_");
+        assert_eq!(idents.get("fn a", "fn_"),       "fn__2");
+    }
+
+    #[test] fn generate_colliding_java_tests() {
+        let tests = [
+            ("com.example", "Foo$Bar", "test$1"),
+            ("com.example", "Foo$Bar", "test_1"),
+            ("com.example", "Foo_Bar", "test$1"),
+        ].iter().map(|(package, class, method)| (package.to_string(), class.to_string(), method.to_string(), JavaTestKind::Static)).collect();
+        assert_eq!(generate_java_tests(&tests).lines().skip(1).collect::<Vec<_>>(), vec![
+            "#[allow(non_snake_case)] mod com {",
+            "    #[allow(non_snake_case)] mod example {",
+            "        #[allow(non_snake_case)] mod Foo_Bar {",
+            "            #[test] fn test_1() { ::jerk::run_test!(\"com.example\", \"Foo$Bar\", \"test$1\"); }",
+            "            #[test] fn test_1_2() { ::jerk::run_test!(\"com.example\", \"Foo$Bar\", \"test_1\"); }",
+            "        }",
+            "        #[allow(non_snake_case)] mod Foo_Bar_2 {",
+            "            #[test] fn test_1() { ::jerk::run_test!(\"com.example\", \"Foo_Bar\", \"test$1\"); }",
+            "        }",
+            "    }",
+            "}",
+        ]);
+    }
+}
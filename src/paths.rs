@@ -191,6 +191,25 @@ pub fn libjvm_dir(java_home: &impl AsRef<Path>) -> Result<PathBuf, io::Error> {
     Err(io::Error::new(io::ErrorKind::NotFound, format!("Could not find {} in expected locations of JAVA_HOME: {}/jre/{{bin,lib/amd64}}/{{client,server}}/", libjvm, java_home.display())))
 }
 
+/// Return the feature version (`8`, `11`, `17`, ...) of a JDK, read from `JAVA_VERSION` in `%JAVA_HOME%\release`.
+///
+/// ```rust
+/// let java_home   = jerk::paths::java_home().unwrap();
+/// let version     = jerk::paths::java_feature_version(&java_home).unwrap();
+/// assert!(version >= 8);
+/// ```
+pub fn java_feature_version(java_home: &impl AsRef<Path>) -> Result<u32, io::Error> {
+    let release = java_home.as_ref().join("release");
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("No valid JAVA_VERSION in {}", release.display()));
+    let text = fs::read_to_string(&release)?;
+    let version = text.lines().find_map(|line| line.strip_prefix("JAVA_VERSION=")).ok_or_else(invalid)?.trim().trim_matches('"');
+    let mut parts = version.split(&['.', '_', '-', '+'][..]);
+    match parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid)? {
+        1       => parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid), // "1.8.0_161" style
+        feature => Ok(feature),
+    }
+}
+
 
 
 fn for_each_dir<T>(dir: &Path, pattern: &str, on_dir: &mut impl FnMut(PathBuf, &str) -> Option<T>) -> Option<T> {
@@ -247,3 +266,29 @@ impl WinPaths {
         }
     }
 }
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn java_feature_version() {
+        let dir = std::env::temp_dir().join(format!("jerk-paths-test-{}-java-feature-version", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let version = |release: &str| {
+            fs::write(dir.join("release"), release).unwrap();
+            super::java_feature_version(&dir).ok()
+        };
+
+        assert_eq!(version("JAVA_VERSION=\"1.8.0_161\"\nOS_NAME=\"Linux\"\n"),                 Some(8));
+        assert_eq!(version("IMPLEMENTOR=\"Eclipse Adoptium\"\r\nJAVA_VERSION=\"17.0.2\"\r\n"),  Some(17));
+        assert_eq!(version("JAVA_VERSION=\"21\"\n"),                                            Some(21));
+        assert_eq!(version("JAVA_VERSION=\"22-ea\"\n"),                                         Some(22));
+        assert_eq!(version("JAVA_VERSION=\"11+28\"\n"),                                         Some(11));
+        assert_eq!(version("JAVA_VERSION=\"\"\n"),                                              None);
+        assert_eq!(version("JAVA_VERSION=\"1\"\n"),                                             None);
+        assert_eq!(version("JAVA_RUNTIME_VERSION=\"17.0.2+8\"\n"),                              None);
+
+        fs::remove_file(dir.join("release")).unwrap();
+        assert_eq!(super::java_feature_version(&dir).unwrap_err().kind(), io::ErrorKind::NotFound);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
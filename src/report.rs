@@ -0,0 +1,113 @@
+//! JUnit XML reports of the Java tests run by [run_test!] and friends, for `JERK_TEST_REPORT`
+//!
+//! libtest has no hook for the end of a test run, so results are collected as tests finish, and written by an `atexit` handler.
+//! The format is the de facto one of Ant's `junitreport`, as understood by Jenkins, GitLab, Azure Pipelines, etc.
+//! Each Java class becomes a `<testsuite>`, so Java results show up separately from the Rust `#[test]`s that ran them.
+
+use crate::test::JavaTestError;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
+
+/// The environment variable naming the directory each test binary writes its `{test binary}.xml` report into.
+pub(crate) const REPORT_VAR : &str = "JERK_TEST_REPORT";
+
+struct TestResult {
+    class:  String,
+    method: String,
+    time:   Duration,
+    error:  Option<JavaTestError>,
+}
+
+lazy_static::lazy_static! { static ref RESULTS : Mutex<Vec<TestResult>> = Mutex::new(Vec::new()); }
+
+/// Run the Java test `package.class.method()`, recording its duration and outcome if `JERK_TEST_REPORT` is set.
+pub(crate) fn time<R>(package: &str, class: &str, method: &str, test: impl FnOnce() -> crate::test::Result<R>) -> crate::test::Result<R> {
+    if report_path().is_none() { return test(); }
+
+    let start = Instant::now();
+    let result = test();
+    let time = start.elapsed();
+
+    static AT_EXIT : Once = Once::new();
+    AT_EXIT.call_once(|| { unsafe { crate::test::atexit(write_at_exit) }; });
+    let class = if package.is_empty() { class.to_string() } else { format!("{}.{}", package, class) };
+    let error = result.as_ref().err().cloned();
+    RESULTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(TestResult { class, method: method.to_string(), time, error });
+    result
+}
+
+fn report_path() -> Option<OsString> {
+    std::env::var_os(REPORT_VAR).filter(|path| !path.is_empty())
+}
+
+extern "C" fn write_at_exit() {
+    let dir = match report_path() { Some(dir) => PathBuf::from(dir), None => return };
+    // A file per test binary, since `cargo test` runs several (one per integration test, plus unit tests and doctests) with the same environment
+    let exe = std::env::current_exe().ok().and_then(|exe| exe.file_stem().map(|stem| stem.to_string_lossy().into_owned()));
+    let path = dir.join(format!("{}.xml", exe.as_deref().unwrap_or("jerk-tests")));
+    let results = RESULTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Err(err) = std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(&path, to_xml(&results))) {
+        eprintln!("jerk: unable to write {} report {}: {}", REPORT_VAR, path.display(), err);
+    }
+}
+
+fn to_xml(results: &[TestResult]) -> String {
+    let mut suites = BTreeMap::<&str, Vec<&TestResult>>::new();
+    for result in results.iter() { suites.entry(&result.class[..]).or_default().push(result); }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    for (class, tests) in suites.iter() {
+        let count = |kind: &str| tests.iter().filter(|test| test.error.as_ref().map(|err| outcome(err).0) == Some(kind)).count();
+        let time = tests.iter().map(|test| test.time).sum::<Duration>();
+        let _ = writeln!(xml, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"0\" time=\"{:.3}\">",
+            escape(class, true), tests.len(), count("failure"), count("error"), time.as_secs_f64());
+        for test in tests.iter() {
+            let _ = write!(xml, "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"", escape(class, true), escape(&test.method, true), test.time.as_secs_f64());
+            match test.error.as_ref() {
+                None => xml.push_str("/>\n"),
+                Some(error) => {
+                    let (kind, ty, message) = outcome(error);
+                    let _ = writeln!(xml, ">\n      <{kind} message=\"{message}\" type=\"{ty}\">{details}</{kind}>\n    </testcase>",
+                        kind = kind, message = escape(&message, true), ty = escape(&ty, true), details = escape(&error.to_string(), false));
+                },
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// (`"failure"` for assertions or `"error"` otherwise, `type`, `message`)
+fn outcome(error: &JavaTestError) -> (&'static str, String, String) {
+    match error {
+        JavaTestError::Exception(exception) => {
+            let assertion = ["AssertionError", "AssertionFailedError", "ComparisonFailure", "MultipleFailuresError"].iter().any(|suffix| exception.class.ends_with(suffix));
+            (if assertion { "failure" } else { "error" }, exception.class.clone(), exception.message.clone().unwrap_or_default())
+        },
+        JavaTestError::Timeout(message) => ("error", String::from("timeout"), message.lines().next().unwrap_or_default().to_string()),
+        other => ("error", String::from("jerk"), other.to_string().lines().next().unwrap_or_default().to_string()),
+    }
+}
+
+/// Escape `text` for XML 1.0 content, or an attribute value.  Characters XML 1.0 can't represent at all are replaced with U+FFFD.
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&'                         => escaped.push_str("&amp;"),
+            '<'                         => escaped.push_str("&lt;"),
+            '>'                         => escaped.push_str("&gt;"),
+            '"' if attribute            => escaped.push_str("&quot;"),
+            '\n' | '\r' | '\t' if attribute => { let _ = write!(escaped, "&#{};", ch as u32); },
+            '\n' | '\r' | '\t'          => escaped.push(ch),
+            '\u{0}' ..= '\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => escaped.push('\u{FFFD}'),
+            ch                          => escaped.push(ch),
+        }
+    }
+    escaped
+}
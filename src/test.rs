@@ -1,24 +1,51 @@
 use crate::*;
 use crate::jvm::JniError;
 use jni_sys::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::convert::*;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
 use std::ptr::null_mut;
 use std::sync::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
 
 pub type Result<T> = std::result::Result<T, JavaTestError>;
 
+/// Why a Java test failed.  See [try_run_test!].
 #[derive(Clone)]
 pub enum JavaTestError {
+    /// Any other failure, e.g. a JNI error while converting arguments, or an isolated test's child process failing
     Unknown(String),
+    /// The test Java VM couldn't be created, or the crate's classes couldn't be loaded into a reused one
+    VmCreation(String),
+    /// The test class couldn't be found on the test class path
+    ClassNotFound(String),
+    /// The test class has no method of that name
+    MethodNotFound(String),
+    /// The test class has a method of that name, but not a static one matching the argument and return types
+    SignatureMismatch(String),
+    /// The Java test threw an exception
+    Exception(JavaException),
+    /// The Java test didn't finish within its timeout.  Includes a thread dump of the Java VM from when it expired.
+    Timeout(String),
     #[doc(hidden)] _NonExhaustive,
 }
 
 impl Display for JavaTestError {
     fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
         match self {
-            JavaTestError::Unknown(message) => write!(fmt, "{}", message),
-            JavaTestError::_NonExhaustive   => write!(fmt, "NonExhaustive"),
+            JavaTestError::Unknown(message)     => write!(fmt, "{}", message),
+            JavaTestError::VmCreation(message)  => write!(fmt, "{}", message),
+            JavaTestError::ClassNotFound(message)       => write!(fmt, "{}", message),
+            JavaTestError::MethodNotFound(message)      => write!(fmt, "{}", message),
+            JavaTestError::SignatureMismatch(message)   => write!(fmt, "{}", message),
+            JavaTestError::Exception(exception) => write!(fmt, "{}", exception),
+            JavaTestError::Timeout(message)     => write!(fmt, "{}", message),
+            JavaTestError::_NonExhaustive       => write!(fmt, "NonExhaustive"),
         }
     }
 }
@@ -42,37 +69,693 @@ impl From<String> for JavaTestError {
     }
 }
 
+impl From<JavaException> for JavaTestError {
+    fn from(value: JavaException) -> Self {
+        JavaTestError::Exception(value)
+    }
+}
+
+impl From<std::io::Error> for JavaTestError {
+    fn from(value: std::io::Error) -> Self {
+        JavaTestError::Unknown(value.to_string())
+    }
+}
+
+
+/// A Java `Throwable`, as thrown by a Java test.
+///
+/// Displays like Java's `Throwable.printStackTrace()`:
+///
+/// ```text
+/// java.lang.IllegalStateException: outer
+///         at com.example.Throws.chained(Throws.java:6)
+/// Caused by: java.lang.IllegalArgumentException: inner
+///         at com.example.Throws.inner(Throws.java:10)
+///         at com.example.Throws.chained(Throws.java:5)
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JavaException {
+    /// The fully qualified class name of the throwable, e.g. `"java.lang.AssertionError"`
+    pub class:          String,
+    /// `getMessage()`
+    pub message:        Option<String>,
+    /// `getStackTrace()`, innermost frame first
+    pub stack_trace:    Vec<JavaStackFrame>,
+    /// `getCause()`
+    pub cause:          Option<Box<JavaException>>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+/// A `java.lang.StackTraceElement`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JavaStackFrame {
+    /// The fully qualified class name, e.g. `"com.example.Adder"`
+    pub class:          String,
+    pub method:         String,
+    pub file:           Option<String>,
+    /// `None` if unknown, `Some(-2)` for native methods
+    pub line:           Option<i32>,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Display for JavaException {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let mut exception = self;
+        loop {
+            write!(fmt, "{}", exception.class)?;
+            if let Some(message) = exception.message.as_ref() { write!(fmt, ": {}", message)?; }
+            for frame in exception.stack_trace.iter() { write!(fmt, "\n\tat {}", frame)?; }
+            match exception.cause.as_ref() {
+                Some(cause) => { write!(fmt, "\nCaused by: ")?; exception = cause; },
+                None        => return Ok(()),
+            }
+        }
+    }
+}
+
+impl Display for JavaStackFrame {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{}.{}(", self.class, self.method)?;
+        match (self.file.as_ref(), self.line) {
+            (_,          Some(-2))      => write!(fmt, "Native Method")?,
+            (Some(file), Some(line))    => write!(fmt, "{}:{}", file, line)?,
+            (Some(file), None)          => write!(fmt, "{}", file)?,
+            (None,       _)             => write!(fmt, "Unknown Source")?,
+        }
+        write!(fmt, ")")
+    }
+}
+
+/// Causes nested deeper than this are dropped (guards against cycles that `getCause()` doesn't prevent)
+const MAX_CAUSES : usize = 32;
+
+/// Retrieve the details of `throwable` through JNI.  There must be no pending exception.
+unsafe fn java_exception(env: *mut JNIEnv, throwable: jthrowable, depth: usize) -> std::io::Result<JavaException> {
+    let _frame = jni::LocalFrame::push(env, 16)?;
+
+    let class = jni::call_object(env, throwable, "getClass\0", "()Ljava/lang/Class;\0", &[])?;
+    let class = jni::call_string(env, class, "getName\0")?.unwrap_or_default();
+    let message = jni::call_string(env, throwable, "getMessage\0")?;
+
+    let trace = jni::call_object(env, throwable, "getStackTrace\0", "()[Ljava/lang/StackTraceElement;\0", &[])?;
+    let stack_trace = java_stack_trace(env, trace)?;
+
+    let cause = jni::call_object(env, throwable, "getCause\0", "()Ljava/lang/Throwable;\0", &[])?;
+    let cause = if cause.is_null() || depth >= MAX_CAUSES || (**env).IsSameObject.unwrap()(env, cause, throwable) == JNI_TRUE {
+        None
+    } else {
+        Some(Box::new(java_exception(env, cause, depth + 1)?))
+    };
+
+    Ok(JavaException { class, message, stack_trace, cause, _non_exhaustive: () })
+}
+
+/// Convert a `StackTraceElement[]` (which may be null) into [JavaStackFrame]s.
+unsafe fn java_stack_trace(env: *mut JNIEnv, trace: jobjectArray) -> std::io::Result<Vec<JavaStackFrame>> {
+    let mut stack_trace = Vec::new();
+    if trace.is_null() { return Ok(stack_trace); }
+    for i in 0 .. (**env).GetArrayLength.unwrap()(env, trace) {
+        let element = (**env).GetObjectArrayElement.unwrap()(env, trace, i);
+        let element = jni::non_null(env, element, "StackTraceElement")?;
+        let line = jni::call_int(env, element, "getLineNumber\0", "()I\0", &[])?;
+        stack_trace.push(JavaStackFrame {
+            class:  jni::call_string(env, element, "getClassName\0")?.unwrap_or_default(),
+            method: jni::call_string(env, element, "getMethodName\0")?.unwrap_or_default(),
+            file:   jni::call_string(env, element, "getFileName\0")?,
+            line:   if line >= 0 || line == -2 { Some(line) } else { None },
+            _non_exhaustive: (),
+        });
+        (**env).DeleteLocalRef.unwrap()(env, element);
+    }
+    Ok(stack_trace)
+}
+
+
+/// A Rust value that can be passed as an argument to a Java test method by [run_test!].
+///
+/// | Rust                                          | Java          |
+/// | --------------------------------------------- | ------------- |
+/// | `bool`, `i8`, `i16`, `i32`, `i64`, `f32`, `f64` | `boolean`, `byte`, `short`, `int`, `long`, `float`, `double` |
+/// | `u16`, `char` (must be in the BMP)            | `char`        |
+/// | `&str`, `String`                              | `String`      |
+/// | `&[T]`, `[T; N]`, `Vec<T>` of the above       | `T[]`         |
+pub trait JavaTestArg {
+    /// The JNI type descriptor of the argument, e.g. `"I"` or `"Ljava/lang/String;"`
+    fn signature(&self) -> String;
+
+    /// Convert to a (local reference, if an object) JNI value.
+    ///
+    /// # Safety
+    /// `env` must be a valid `JNIEnv` attached to the current thread.
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue>;
+}
+
+/// A Rust type that the return value of a Java test method can be converted to by [run_test!].
+///
+/// Implemented for `()` (void), the primitive and string types supported by [JavaTestArg], `Option<String>` (for nullable strings), and `Vec`s of them.
+pub trait JavaTestReturn : Sized {
+    /// The JNI type descriptor of the return type, e.g. `"V"` or `"[I"`
+    fn signature() -> String;
+
+    /// Call the static method `method` of `class` with `CallStatic*MethodA`, without checking for exceptions.
+    ///
+    /// # Safety
+    /// `env` must be a valid `JNIEnv` attached to the current thread, and `method` must return [signature](#tymethod.signature).
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue;
+
+    /// Convert the result of [call_static](#tymethod.call_static), if no exception was thrown.
+    ///
+    /// # Safety
+    /// `env` must be a valid `JNIEnv` attached to the current thread, and `value` must have been returned by [call_static](#tymethod.call_static).
+    unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self>;
+
+    #[doc(hidden)] fn void() -> Option<Self> { None }
+}
+
+/// An element of a Java array argument (`&[T]`, `[T; N]`, `Vec<T>`) for [run_test!].
+pub trait JavaTestArrayElement : Sized {
+    /// The JNI type descriptor of the element type, e.g. `"I"`
+    fn element_signature() -> &'static str;
+
+    /// Create a (local reference to a) new Java array containing `values`.
+    ///
+    /// # Safety
+    /// `env` must be a valid `JNIEnv` attached to the current thread.
+    unsafe fn new_array(env: *mut JNIEnv, values: &[Self]) -> Result<jobject>;
+}
+
+impl<A: JavaTestArg + ?Sized> JavaTestArg for &A {
+    fn signature(&self) -> String { (**self).signature() }
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue> { (**self).to_jvalue(env) }
+}
+
+impl<T: JavaTestArrayElement> JavaTestArg for [T] {
+    fn signature(&self) -> String { format!("[{}", T::element_signature()) }
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue> { Ok(jvalue { l: T::new_array(env, self)? }) }
+}
+
+impl<T: JavaTestArrayElement, const N: usize> JavaTestArg for [T; N] {
+    fn signature(&self) -> String { self[..].signature() }
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue> { self[..].to_jvalue(env) }
+}
+
+impl<T: JavaTestArrayElement> JavaTestArg for Vec<T> {
+    fn signature(&self) -> String { self[..].signature() }
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue> { self[..].to_jvalue(env) }
+}
+
+impl JavaTestReturn for () {
+    fn signature() -> String { String::from("V") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+        (**env).CallStaticVoidMethodA.unwrap()(env, class, method, args.as_ptr());
+        jvalue { l: null_mut() }
+    }
+    unsafe fn from_jvalue(_env: *mut JNIEnv, _value: jvalue) -> Result<Self> { Ok(()) }
+    fn void() -> Option<Self> { Some(()) }
+}
+
+macro_rules! java_test_primitives {
+    ( $( $rust:ty => $sig:literal, $field:ident, $call_static:ident, $new_array:ident, $set_region:ident, $get_region:ident ; )* ) => {$(
+        impl JavaTestArg for $rust {
+            fn signature(&self) -> String { String::from($sig) }
+            unsafe fn to_jvalue(&self, _env: *mut JNIEnv) -> Result<jvalue> { Ok(jvalue { $field: *self }) }
+        }
+
+        impl JavaTestReturn for $rust {
+            fn signature() -> String { String::from($sig) }
+            unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+                jvalue { $field: (**env).$call_static.unwrap()(env, class, method, args.as_ptr()) }
+            }
+            unsafe fn from_jvalue(_env: *mut JNIEnv, value: jvalue) -> Result<Self> { Ok(value.$field) }
+        }
+
+        impl JavaTestArrayElement for $rust {
+            fn element_signature() -> &'static str { $sig }
+            unsafe fn new_array(env: *mut JNIEnv, values: &[Self]) -> Result<jobject> {
+                let array = (**env).$new_array.unwrap()(env, values.len() as jsize);
+                let array = jni::non_null(env, array, "array")?;
+                (**env).$set_region.unwrap()(env, array, 0, values.len() as jsize, values.as_ptr());
+                Ok(array)
+            }
+        }
+
+        impl JavaTestReturn for Vec<$rust> {
+            fn signature() -> String { format!("[{}", $sig) }
+            unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+                jvalue { l: (**env).CallStaticObjectMethodA.unwrap()(env, class, method, args.as_ptr()) }
+            }
+            unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self> {
+                if value.l.is_null() { return Err("Java test returned a null array".into()); }
+                let len = (**env).GetArrayLength.unwrap()(env, value.l);
+                let mut values = vec![Default::default(); len as usize];
+                (**env).$get_region.unwrap()(env, value.l, 0, len, values.as_mut_ptr());
+                Ok(values)
+            }
+        }
+    )*};
+}
 
-/// Execute a Java unit test.  The method must be static, return void, and take no arguments.
+java_test_primitives! {
+    i8  => "B", b, CallStaticByteMethodA,   NewByteArray,   SetByteArrayRegion,   GetByteArrayRegion;
+    i16 => "S", s, CallStaticShortMethodA,  NewShortArray,  SetShortArrayRegion,  GetShortArrayRegion;
+    u16 => "C", c, CallStaticCharMethodA,   NewCharArray,   SetCharArrayRegion,   GetCharArrayRegion;
+    i32 => "I", i, CallStaticIntMethodA,    NewIntArray,    SetIntArrayRegion,    GetIntArrayRegion;
+    i64 => "J", j, CallStaticLongMethodA,   NewLongArray,   SetLongArrayRegion,   GetLongArrayRegion;
+    f32 => "F", f, CallStaticFloatMethodA,  NewFloatArray,  SetFloatArrayRegion,  GetFloatArrayRegion;
+    f64 => "D", d, CallStaticDoubleMethodA, NewDoubleArray, SetDoubleArrayRegion, GetDoubleArrayRegion;
+}
+
+impl JavaTestArg for bool {
+    fn signature(&self) -> String { String::from("Z") }
+    unsafe fn to_jvalue(&self, _env: *mut JNIEnv) -> Result<jvalue> { Ok(jvalue { z: if *self { JNI_TRUE } else { JNI_FALSE } }) }
+}
+
+impl JavaTestReturn for bool {
+    fn signature() -> String { String::from("Z") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+        jvalue { z: (**env).CallStaticBooleanMethodA.unwrap()(env, class, method, args.as_ptr()) }
+    }
+    unsafe fn from_jvalue(_env: *mut JNIEnv, value: jvalue) -> Result<Self> { Ok(value.z != JNI_FALSE) }
+}
+
+impl JavaTestArrayElement for bool {
+    fn element_signature() -> &'static str { "Z" }
+    unsafe fn new_array(env: *mut JNIEnv, values: &[Self]) -> Result<jobject> {
+        let values = values.iter().map(|&v| if v { JNI_TRUE } else { JNI_FALSE }).collect::<Vec<jboolean>>();
+        let array = (**env).NewBooleanArray.unwrap()(env, values.len() as jsize);
+        let array = jni::non_null(env, array, "array")?;
+        (**env).SetBooleanArrayRegion.unwrap()(env, array, 0, values.len() as jsize, values.as_ptr());
+        Ok(array)
+    }
+}
+
+impl JavaTestReturn for Vec<bool> {
+    fn signature() -> String { String::from("[Z") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+        jvalue { l: (**env).CallStaticObjectMethodA.unwrap()(env, class, method, args.as_ptr()) }
+    }
+    unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self> {
+        if value.l.is_null() { return Err("Java test returned a null array".into()); }
+        let len = (**env).GetArrayLength.unwrap()(env, value.l);
+        let mut values = vec![JNI_FALSE; len as usize];
+        (**env).GetBooleanArrayRegion.unwrap()(env, value.l, 0, len, values.as_mut_ptr());
+        Ok(values.into_iter().map(|v| v != JNI_FALSE).collect())
+    }
+}
+
+/// A Java `char` is a single UTF-16 code unit:  supplementary characters (outside the BMP) can't be represented, and unpaired surrogates aren't Rust `char`s.
+fn java_char(ch: char) -> Result<jchar> {
+    let mut utf16 = [0; 2];
+    match ch.encode_utf16(&mut utf16) {
+        [unit] => Ok(*unit),
+        _ => Err(format!("{:?} (U+{:04X}) is outside the Basic Multilingual Plane, and can't be passed as a Java char", ch, ch as u32).into()),
+    }
+}
+
+fn rust_char(unit: jchar) -> Result<char> {
+    std::char::from_u32(u32::from(unit)).ok_or_else(|| format!("Java test returned an unpaired surrogate char (U+{:04X}), use u16 instead of char", unit).into())
+}
+
+impl JavaTestArg for char {
+    fn signature(&self) -> String { String::from("C") }
+    unsafe fn to_jvalue(&self, _env: *mut JNIEnv) -> Result<jvalue> { Ok(jvalue { c: java_char(*self)? }) }
+}
+
+impl JavaTestReturn for char {
+    fn signature() -> String { String::from("C") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue { u16::call_static(env, class, method, args) }
+    unsafe fn from_jvalue(_env: *mut JNIEnv, value: jvalue) -> Result<Self> { rust_char(value.c) }
+}
+
+impl JavaTestArrayElement for char {
+    fn element_signature() -> &'static str { "C" }
+    unsafe fn new_array(env: *mut JNIEnv, values: &[Self]) -> Result<jobject> {
+        u16::new_array(env, &values.iter().map(|&ch| java_char(ch)).collect::<Result<Vec<jchar>>>()?)
+    }
+}
+
+impl JavaTestReturn for Vec<char> {
+    fn signature() -> String { String::from("[C") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue { Vec::<u16>::call_static(env, class, method, args) }
+    unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self> {
+        Vec::<u16>::from_jvalue(env, value)?.into_iter().map(rust_char).collect()
+    }
+}
+
+impl JavaTestArg for str {
+    fn signature(&self) -> String { String::from("Ljava/lang/String;") }
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue> { Ok(jvalue { l: jni::new_string(env, self)? }) }
+}
+
+impl JavaTestArg for String {
+    fn signature(&self) -> String { self.as_str().signature() }
+    unsafe fn to_jvalue(&self, env: *mut JNIEnv) -> Result<jvalue> { self.as_str().to_jvalue(env) }
+}
+
+impl JavaTestReturn for Option<String> {
+    fn signature() -> String { String::from("Ljava/lang/String;") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+        jvalue { l: (**env).CallStaticObjectMethodA.unwrap()(env, class, method, args.as_ptr()) }
+    }
+    unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self> {
+        Ok(if value.l.is_null() { None } else { Some(jni::get_string(env, value.l)) })
+    }
+}
+
+impl JavaTestReturn for String {
+    fn signature() -> String { Option::<String>::signature() }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue { Option::<String>::call_static(env, class, method, args) }
+    unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self> {
+        Option::<String>::from_jvalue(env, value)?.ok_or_else(|| "Java test returned a null String".into())
+    }
+}
+
+impl JavaTestArrayElement for &str {
+    fn element_signature() -> &'static str { "Ljava/lang/String;" }
+    unsafe fn new_array(env: *mut JNIEnv, values: &[Self]) -> Result<jobject> {
+        let class = jni::find_class(env, "java/lang/String\0")?;
+        let array = (**env).NewObjectArray.unwrap()(env, values.len() as jsize, class, null_mut());
+        let array = jni::non_null(env, array, "array")?;
+        for (i, value) in values.iter().enumerate() {
+            let string = jni::new_string(env, value)?;
+            (**env).SetObjectArrayElement.unwrap()(env, array, i as jsize, string);
+            (**env).DeleteLocalRef.unwrap()(env, string);
+        }
+        Ok(array)
+    }
+}
+
+impl JavaTestArrayElement for String {
+    fn element_signature() -> &'static str { <&str>::element_signature() }
+    unsafe fn new_array(env: *mut JNIEnv, values: &[Self]) -> Result<jobject> {
+        <&str>::new_array(env, &values.iter().map(|v| v.as_str()).collect::<Vec<&str>>())
+    }
+}
+
+impl JavaTestReturn for Vec<String> {
+    fn signature() -> String { String::from("[Ljava/lang/String;") }
+    unsafe fn call_static(env: *mut JNIEnv, class: jclass, method: jmethodID, args: &[jvalue]) -> jvalue {
+        jvalue { l: (**env).CallStaticObjectMethodA.unwrap()(env, class, method, args.as_ptr()) }
+    }
+    unsafe fn from_jvalue(env: *mut JNIEnv, value: jvalue) -> Result<Self> {
+        if value.l.is_null() { return Err("Java test returned a null array".into()); }
+        let len = (**env).GetArrayLength.unwrap()(env, value.l);
+        let mut values = Vec::with_capacity(len as usize);
+        for i in 0 .. len {
+            let string = (**env).GetObjectArrayElement.unwrap()(env, value.l, i);
+            values.push(jni::get_string(env, string));
+            (**env).DeleteLocalRef.unwrap()(env, string);
+        }
+        Ok(values)
+    }
+}
+
+
+/// Execute a Java unit test.  The method must be static.
+///
+/// Any additional arguments are passed to the method, and its signature is derived from their types (see [JavaTestArg].)
+/// The method must return void, unless a return type (see [JavaTestReturn]) is given after `=>`:
+///
+/// ```ignore
+/// jerk::run_test!("com.example", "Adder", "test");                                    // static void test()
+/// jerk::run_test!("com.example", "Adder", "check", 1, 2, 3);                          // static void check(int, int, int)
+/// assert_eq!(jerk::run_test!("com.example", "Adder", "add", 1, 2 => i32), 3);         // static int add(int, int)
+/// assert_eq!(jerk::run_test!("com.example", "Adder", "sum", &[1, 2, 3] => i64), 6);   // static long sum(int[])
+/// let hello = jerk::run_test!("com.example", "Hello", "greet", "world" => String);    // static String greet(String)
+/// ```
+///
+/// All tests share a single in-process Java VM.  If something else already created a Java VM in this process (e.g. a Java host
+/// that loaded the crate as a native library), that VM is reused, and the crate's `.jar` is loaded into it through a separate
+/// `URLClassLoader` that test classes are resolved with.  Set `JERK_TEST_ISOLATED=1` to run every void test in its own process instead,
+/// as if [run_test_isolated!] had been used.
+///
+/// # Timeouts
+///
+/// A test that deadlocks or loops forever would otherwise hang `cargo test`.  Give `run_test!` a timeout to fail it instead:
+///
+/// ```ignore
+/// jerk::run_test!(timeout = std::time::Duration::from_secs(10); "com.example", "Adder", "test");
+/// ```
+///
+/// Or set a default timeout (in seconds) for every test with the `JERK_TEST_TIMEOUT` environment variable.  When the timeout expires,
+/// the test fails with a dump of every Java thread's stack, and its Java thread is interrupted so the rest of the tests can continue.
+/// If interrupting (or, failing that, `Thread.stop()`ing) the thread doesn't end the test within a few seconds, the dump is printed
+/// and the test process exits, since the `#[test]` can't be unblocked.
+///
+/// # Reports
+///
+/// Set `JERK_TEST_REPORT` to a directory to write JUnit XML reports of every Java test run (class, method, duration, and any failure)
+/// into it.  Each test binary writes its own `{binary name}.xml` (e.g. `test-0123456789abcdef.xml`) when it exits, creating the directory
+/// if needed, so point CI at `JERK_TEST_REPORT/*.xml`.
 #[macro_export] macro_rules! run_test {
-    ( $package:expr, $class:expr, $method:expr ) => {{
-        $crate::test::run_test_impl(env!("JERK_BUILD_JAR"), $package, $class, $method).unwrap()
+    ( $($args:tt)* ) => { $crate::try_run_test!($($args)*).unwrap() };
+}
+
+/// Execute a Java unit test like [run_test!], but return a [Result](crate::test::Result) instead of panicking on failure.
+///
+/// ```ignore
+/// use jerk::test::JavaTestError;
+/// match jerk::try_run_test!("com.example", "Adder", "add", 1, 2 => i32) {
+///     Ok(sum)                                 => assert_eq!(sum, 3),
+///     Err(JavaTestError::Exception(e))        => panic!("Adder.add threw {}", e.class),
+///     Err(JavaTestError::ClassNotFound(_))    => eprintln!("skipping: Adder not built"),
+///     Err(other)                              => panic!("{}", other),
+/// }
+/// ```
+#[macro_export] macro_rules! try_run_test {
+    ( timeout = $timeout:expr; $package:expr, $class:expr, $method:expr $(, $arg:expr)* => $ret:ty ) => {
+        $crate::test::run_test_timeout_impl::<$ret>(&$crate::test_name!(), Some($timeout), $crate::test_classpath!(), $package, $class, $method, &[$(&$arg as &dyn $crate::test::JavaTestArg),*])
+    };
+    ( timeout = $timeout:expr; $package:expr, $class:expr, $method:expr $(, $arg:expr)* $(,)? ) => {
+        $crate::test::run_test_timeout_impl::<()>(&$crate::test_name!(), Some($timeout), $crate::test_classpath!(), $package, $class, $method, &[$(&$arg as &dyn $crate::test::JavaTestArg),*])
+    };
+    ( $package:expr, $class:expr, $method:expr $(, $arg:expr)* => $ret:ty ) => {
+        $crate::test::run_test_impl::<$ret>(&$crate::test_name!(), $crate::test_classpath!(), $package, $class, $method, &[$(&$arg as &dyn $crate::test::JavaTestArg),*])
+    };
+    ( $package:expr, $class:expr, $method:expr $(, $arg:expr)* $(,)? ) => {
+        $crate::test::run_test_impl::<()>(&$crate::test_name!(), $crate::test_classpath!(), $package, $class, $method, &[$(&$arg as &dyn $crate::test::JavaTestArg),*])
+    };
+}
+
+/// Execute a Java unit test in a child process.  The method must be static and return void.  Arguments are passed as with [run_test!].
+///
+/// The test binary re-executes itself, filtered down to the current `#[test]`, and only this Java test is run in the child.
+/// A crash, `System.exit(...)`, or native segfault is reported as a failure of this test, along with the child's output,
+/// instead of taking down the entire test binary.
+///
+/// Rust code around the macro runs in both processes, but other `run_test!`s in the same `#[test]` are skipped by the child.
+///
+/// The current `#[test]` is the name libtest gave the current thread, or (on the main thread, which older versions of libtest run tests on
+/// with `--test-threads=1`) the function the macro was expanded in.  When calling it from a helper function, or from a thread of your own,
+/// pass libtest's name for the `#[test]` explicitly instead:
+///
+/// ```ignore
+/// fn run_isolated(test: &str, method: &str) {
+///     jerk::run_test_isolated!(name = test; "com.example", "Adder", method);
+/// }
+///
+/// #[test] fn adder() { run_isolated("tests::adder", "test"); }
+/// ```
+#[macro_export] macro_rules! run_test_isolated {
+    ( name = $name:expr; $package:expr, $class:expr, $method:expr $(, $arg:expr)* $(,)? ) => {{
+        $crate::test::run_test_isolated_impl($name, $crate::test_classpath!(), $package, $class, $method, &[$(&$arg as &dyn $crate::test::JavaTestArg),*]).unwrap()
+    }};
+    ( $package:expr, $class:expr, $method:expr $(, $arg:expr)* $(,)? ) => {{
+        $crate::test::run_test_isolated_impl(&$crate::test_name!(), $crate::test_classpath!(), $package, $class, $method, &[$(&$arg as &dyn $crate::test::JavaTestArg),*]).unwrap()
+    }};
+}
+
+/// Execute a JUnit 4 or JUnit 5 (Jupiter, or any other engine on the JUnit Platform) test method, in the same Java VM as [run_test!].
+///
+/// JUnit itself isn't bundled: add its jars to [Metabuild::class_paths](crate::Metabuild::class_paths).  The test passes if JUnit reports
+/// it as successful, ignored, or skipped, and otherwise fails with JUnit's failure (typically an `AssertionError`) as a [JavaException].
+///
+/// ```ignore
+/// jerk::run_junit_test!("com.example", "AdderTest", "addsSmallNumbers");
+/// ```
+///
+/// `@Test` methods are also discovered automatically, see [metabuild()](crate::metabuild)'s "Java tests".
+#[macro_export] macro_rules! run_junit_test {
+    ( $package:expr, $class:expr, $method:expr $(,)? ) => {{
+        $crate::test::run_junit_test_impl(&$crate::test_name!(), $crate::test_classpath!(), $package, $class, $method).unwrap()
     }};
 }
 
+/// libtest's name for the current `#[test]` (`module::test_fn`), which isolated tests re-run themselves with.
+#[doc(hidden)] #[macro_export] macro_rules! test_name {
+    () => {{ fn f() {} $crate::test::test_name_of(f) }};
+}
+
+/// The current thread's name, which libtest names after the test it runs.  On the main thread (as older versions of libtest use
+/// with `--test-threads=1`), guess from the type name of `f` instead:  `"crate::module::test_fn::f"` -> `"module::test_fn"`.
+/// That guess is only right if the macro was expanded directly in the `#[test]`, and `type_name`'s format isn't guaranteed.
+#[doc(hidden)] pub fn test_name_of<F>(_f: F) -> String {
+    match std::thread::current().name() {
+        Some("main") | None => {},
+        Some(name)          => return name.to_string(),
+    }
+    let mut path = std::any::type_name::<F>().split("::").skip(1).filter(|segment| *segment != "{{closure}}").collect::<Vec<_>>();
+    path.pop();
+    path.join("::")
+}
+
+/// The `java.class.path` for the test Java VM:  `JERK_BUILD_CLASSPATH` if [Metabuild](crate::Metabuild) set it, otherwise just `JERK_BUILD_JAR`.
+#[doc(hidden)] #[macro_export] macro_rules! test_classpath {
+    () => { match option_env!("JERK_BUILD_CLASSPATH") { Some(classpath) => classpath, None => env!("JERK_BUILD_JAR") } };
+}
+
 #[doc(hidden)]
-pub fn run_test_impl(jar: &str, package: &str, class: &str, method: &str) -> Result<()> {
-    let mut reused_vm = false;
+pub fn run_junit_test_impl(test: &str, classpath: &str, package: &str, class: &str, method: &str) -> Result<()> {
+    let qualified = if package.is_empty() { class.to_string() } else { format!("{}.{}", package, class) };
+    let timeout = default_timeout()?;
+    let isolated = isolated_by_env();
+    report::time(package, class, method, || run_test_dispatch(test, classpath, "com.maulingmonkey.jerk", "JUnit", "run", &[&qualified, &method], isolated, timeout))
+}
+
+#[doc(hidden)]
+pub fn run_test_impl<R: JavaTestReturn>(test: &str, classpath: &str, package: &str, class: &str, method: &str, args: &[&dyn JavaTestArg]) -> Result<R> {
+    run_test_timeout_impl(test, None, classpath, package, class, method, args)
+}
+
+#[doc(hidden)]
+pub fn run_test_timeout_impl<R: JavaTestReturn>(test: &str, timeout: Option<Duration>, classpath: &str, package: &str, class: &str, method: &str, args: &[&dyn JavaTestArg]) -> Result<R> {
+    let isolated = isolated_by_env();
+    let timeout = match timeout { Some(timeout) => Some(timeout), None => default_timeout()? };
+    report::time(package, class, method, || run_test_dispatch(test, classpath, package, class, method, args, isolated, timeout))
+}
+
+#[doc(hidden)]
+pub fn run_test_isolated_impl(test: &str, classpath: &str, package: &str, class: &str, method: &str, args: &[&dyn JavaTestArg]) -> Result<()> {
+    let timeout = default_timeout()?;
+    report::time(package, class, method, || run_test_dispatch(test, classpath, package, class, method, args, true, timeout))
+}
+
+/// `JERK_TEST_ISOLATED` is set (and not `0`)
+fn isolated_by_env() -> bool {
+    std::env::var_os("JERK_TEST_ISOLATED").map_or(false, |v| !v.is_empty() && v != "0")
+}
+
+/// `JERK_TEST_TIMEOUT`, in seconds
+fn default_timeout() -> Result<Option<Duration>> {
+    match std::env::var("JERK_TEST_TIMEOUT").as_deref() {
+        Err(_) | Ok("") => Ok(None),
+        Ok(secs)        => match secs.parse::<f64>() {
+            Ok(secs) if secs.is_finite() && secs > 0.0  => Ok(Some(Duration::from_secs_f64(secs))),
+            _                                           => Err(format!("JERK_TEST_TIMEOUT={:?} is invalid, expected a positive number of seconds", secs).into()),
+        },
+    }
+}
+
+/// Set in the child process to `{index} {package}.{class}.{method}` of the one `run_test!` it should execute.
+const ISOLATED_CHILD_VAR : &str = "JERK_TEST_ISOLATED_CHILD";
+/// Printed by the child process after the test passed, so `System.exit(0)` isn't mistaken for success.
+const ISOLATED_CHILD_OK : &str = "jerk: isolated test passed";
+
+// The current #[test], and how many run_test!s it has executed so far.  Keyed by name, since libtest reuses the main thread with --test-threads=1.
+thread_local! { static RUN_TEST_INDEX : RefCell<(String, usize)> = RefCell::new((String::new(), 0)); }
+
+/// Only void tests can be isolated, since there's no way to return a value from the child process.
+#[allow(clippy::too_many_arguments)]
+fn run_test_dispatch<R: JavaTestReturn>(test: &str, classpath: &str, package: &str, class: &str, method: &str, args: &[&dyn JavaTestArg], isolated: bool, timeout: Option<Duration>) -> Result<R> {
+    let index = RUN_TEST_INDEX.with(|i| {
+        let mut i = i.borrow_mut();
+        if i.0 != test { *i = (test.to_string(), 0); }
+        i.1 += 1;
+        i.1 - 1
+    });
+    let target = format!("{} {}.{}.{}", index, package, class, method);
+
+    if let Some(child) = std::env::var_os(ISOLATED_CHILD_VAR) {
+        if child != target.as_str() {
+            // Skip other void tests, but the Rust code in the child may depend on the results of non-void ones
+            return match R::void() { Some(void) => Ok(void), None => run_test_in_process(test, classpath, package, class, method, args, timeout) };
+        }
+        // Any run_test!s invoked from within the test itself (e.g. by native code) should just run normally
+        std::env::remove_var(ISOLATED_CHILD_VAR);
+        match run_test_in_process::<R>(test, classpath, package, class, method, args, timeout) {
+            Ok(_) => {
+                println!("{}", ISOLATED_CHILD_OK);
+                let _ = std::io::stdout().flush();
+                std::process::exit(0);
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(101);
+            },
+        }
+    } else {
+        match R::void() {
+            Some(void) if isolated  => run_test_in_child(test, &target, package, class, method).map(|()| void),
+            _                       => run_test_in_process(test, classpath, package, class, method, args, timeout),
+        }
+    }
+}
+
+fn run_test_in_child(test: &str, target: &str, package: &str, class: &str, method: &str) -> Result<()> {
+    if test.is_empty() { return Err(format!("{}.{}.{}(): unable to determine the name of the current #[test] to isolate it", package, class, method).into()); }
+    let exe = std::env::current_exe().map_err(|err| format!("{}.{}.{}(): unable to locate the test executable: {}", package, class, method, err))?;
+
+    let output = Command::new(exe)
+        .args([test, "--exact", "--nocapture", "--test-threads=1"])
+        .env(ISOLATED_CHILD_VAR, target)
+        .env_remove(report::REPORT_VAR) // reported by this process instead
+        .output()
+        .map_err(|err| format!("{}.{}.{}(): unable to spawn isolated test process: {}", package, class, method, err))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let passed = output.status.success() && stdout.lines().any(|line| line.ends_with(ISOLATED_CHILD_OK));
+    if passed { return Ok(()); }
+
+    let reason = match output.status.code() {
+        Some(0)     => String::from("exited with code 0 without finishing the test (System.exit(0)?)"),
+        Some(code)  => format!("exited with code {}", code),
+        None        => format!("terminated abnormally ({})", output.status),
+    };
+    Err(format!(
+        "{}.{}.{}() failed in isolated test process: {}\n--- stdout ---\n{}\n--- stderr ---\n{}",
+        package, class, method, reason, stdout.trim_end(), stderr.trim_end(),
+    ).into())
+}
+
+fn run_test_in_process<R: JavaTestReturn>(test: &str, classpath: &str, package: &str, class: &str, method: &str, args: &[&dyn JavaTestArg], timeout: Option<Duration>) -> Result<R> {
     {
         let mut vm = VM.lock().unwrap();
-        if vm.is_null() {
-            **vm = create_java_vm(jar, &mut reused_vm);
+        if vm.is_none() {
+            let mut reused_vm = false;
+            let new_vm = create_java_vm(classpath, &mut reused_vm)?;
+            if reused_vm {
+                // Someone else created the VM, without our .jar on its java.class.path
+                let loader = new_class_loader(&new_vm, &java_vm_classpath(classpath))
+                    .map_err(|err| JavaTestError::VmCreation(format!("Unable to load {} into the existing Java VM: {}", classpath, err)))?;
+                *CLASS_LOADER.lock().unwrap() = Some(loader);
+            } else if VM_OPTIONS.lock().unwrap().capture_output {
+                new_vm.with_env(|env| unsafe { capture_output(env) })
+                    .map_err(|err| JavaTestError::VmCreation(format!("Unable to attach to the test Java VM: {}", err)))?
+                    .map_err(|err| JavaTestError::VmCreation(format!("Unable to redirect System.out and System.err of the test Java VM: {}", err)))?;
+            }
+            *vm = Some(Box::leak(Box::new(new_vm))); // never destroyed:  most JDKs can't create another Java VM in the same process anyways
         }
     }
+    let shared_loader = CLASS_LOADER.lock().unwrap().as_ref().map_or(null_mut(), |loader| loader.as_raw());
 
     let env = test_thread_env();
-    if env == null_mut() { return Err("Couldn't initialize Java VM".into()); }
+    if env.is_null() { return Err(JavaTestError::VmCreation("Couldn't attach to the test Java VM".into())); }
+    let loader = unsafe { test_class_loader(env, shared_loader, test, classpath, package, class) }?;
 
-    let class_id    = format!("{}/{}\0", package.replace(".", "/"), class);
     let method_id   = format!("{}\0", method);
+    let signature   = format!("({}){}\0", args.iter().map(|arg| arg.signature()).collect::<String>(), R::signature());
 
     // Safety:
     // * `**env` must be valid (non-null, not dangling, valid fn pointers if present)
     // * string IDs must be `\0` terminated
     unsafe {
-        let class_id = (**env).FindClass.unwrap()(env, class_id.as_ptr() as *const _);
+        let _frame = jni::LocalFrame::push(env, 16 + args.len() as jint)?;
+        let class_id = find_test_class(env, loader, package, class);
         if class_id.is_null() {
-            panic!(
+            return Err(JavaTestError::ClassNotFound(format!(
                 concat!(
                     "Failed to find class {package}.{class}.  Possible causes:\n",
                     "{reused_vm_warning}",
@@ -81,68 +764,573 @@ pub fn run_test_impl(jar: &str, package: &str, class: &str, method: &str) -> Res
                     "  - The corresponding .jar may not be have been found (are you using `jerk_build::metabuild()` in your build.rs?)\n",
                 ),
                 package=package, class=class,
-                reused_vm_warning = if reused_vm { "  - WARNING: jerk::run_test! was unable to start a new JVM / specify a java.class.path.  The .jar may not have been loaded!\n" } else { "" },
-            );
+                reused_vm_warning = if !shared_loader.is_null() { "  - NOTE: jerk::run_test! reused an existing JVM, and loaded the .jar through a separate URLClassLoader.\n" } else { "" },
+            )));
+        }
+        let method_id   = (**env).GetStaticMethodID.unwrap()(env, class_id, method_id.as_ptr() as *const _, signature.as_ptr() as *const _);
+        if method_id.is_null() {
+            (**env).ExceptionClear.unwrap()(env); // NoSuchMethodError
+            let signature = signature.trim_end_matches('\0');
+            return Err(match declared_methods(env, class_id, method) {
+                Ok(methods) if methods.is_empty()   => JavaTestError::MethodNotFound(format!("{}.{}.{}: no such method", package, class, method)),
+                Ok(methods)                         => JavaTestError::SignatureMismatch(format!(
+                    "{}.{}.{}: no static method with signature {}, only:\n  {}", package, class, method, signature, methods.join("\n  ")
+                )),
+                Err(err)                            => JavaTestError::MethodNotFound(format!("{}.{}.{}: no static method with signature {} ({})", package, class, method, signature, err)),
+            });
+        }
+        let args = args.iter().map(|arg| arg.to_jvalue(env)).collect::<Result<Vec<jvalue>>>()?;
+        let watchdog = match timeout {
+            Some(timeout)   => Some(Watchdog::start(env, timeout, format!("{}.{}.{}()", package, class, method))?),
+            None            => None,
+        };
+        let result = R::call_static(env, class_id, method_id, &args);
+        if let Some(timed_out) = watchdog.and_then(|watchdog| watchdog.finish()) {
+            (**env).ExceptionClear.unwrap()(env); // most likely the InterruptedException or ThreadDeath that ended the test
+            clear_interrupted(env)?;
+            return Err(JavaTestError::Timeout(timed_out));
         }
-        let method_id   = (**env).GetStaticMethodID.unwrap()(env, class_id, method_id.as_ptr() as *const _, "()V\0".as_ptr() as *const _);
-        assert_ne!(method_id, null_mut(), "Failed to GetStaticMethodID {}.{}", class, method);
-        (**env).CallStaticVoidMethodA.unwrap()(env, class_id, method_id, [].as_ptr());
         if (**env).ExceptionCheck.unwrap()(env) == JNI_TRUE {
-            (**env).ExceptionDescribe.unwrap()(env);
+            let throwable = (**env).ExceptionOccurred.unwrap()(env);
             (**env).ExceptionClear.unwrap()(env);
-            Err(format!("{}.{}() threw a Java Exception", class, method).into())
+            match java_exception(env, throwable, 0) {
+                Ok(exception)   => Err(exception.into()),
+                Err(err)        => Err(format!("{}.{}() threw a Java Exception (unable to retrieve details: {})", class, method, err).into()),
+            }
         } else {
-            Ok(())
+            R::from_jvalue(env, result)
         }
     }
 }
 
+/// How long [Watchdog] waits for a timed out test to end after interrupting (and again after stopping) its thread
+const TIMEOUT_GRACE : Duration = Duration::from_secs(5);
+
+/// Interrupts the Java test running on the current thread if it takes longer than `timeout`.
+struct Watchdog {
+    done:   mpsc::Sender<()>,
+    thread: std::thread::JoinHandle<Option<String>>,
+}
+
+impl Watchdog {
+    unsafe fn start(env: *mut JNIEnv, timeout: Duration, test: String) -> Result<Self> {
+        let thread_class    = jni::find_class(env, "java/lang/Thread\0")?;
+        let current_thread  = jni::static_method(env, thread_class, "currentThread\0", "()Ljava/lang/Thread;\0")?;
+        let java_thread     = (**env).CallStaticObjectMethodA.unwrap()(env, thread_class, current_thread, [].as_ptr());
+        let java_thread     = jni::non_null(env, java_thread, "Thread.currentThread()")?;
+        let java_thread     = jni::GlobalRef::new(env, java_thread)?;
+        let vm              = vm().ok_or("jerk: the test Java VM hasn't been created yet")?;
+
+        let (done, finished) = mpsc::channel();
+        let thread = std::thread::Builder::new().name(format!("jerk watchdog for {}", test)).spawn(move || {
+            let timed_out = match finished.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout)  => Some(Self::expire(vm, java_thread.as_raw(), &finished, timeout, &test)),
+                _                               => None,
+            };
+            drop(java_thread); // attaches just long enough to DeleteGlobalRef
+            timed_out
+        })?;
+        Ok(Self { done, thread })
+    }
+
+    /// Stop watching.  Returns the failure message (including the thread dump) if the test timed out.
+    fn finish(self) -> Option<String> {
+        let _ = self.done.send(());
+        self.thread.join().unwrap_or(None)
+    }
+
+    /// Dump all threads, then interrupt `java_thread` and wait for the test to end.  Exits the process if it never does.
+    unsafe fn expire(vm: &jvm::Vm, java_thread: jobject, finished: &mpsc::Receiver<()>, timeout: Duration, test: &str) -> String {
+        let guard = vm.attach_current_thread(Some("jerk watchdog"));
+        let dump = match guard.as_ref().map(|guard| thread_dump(guard.env())) {
+            Ok(Ok(dump))    => dump,
+            Ok(Err(err))    => format!("(unable to dump threads: {})", err),
+            Err(err)        => format!("(unable to dump threads: {})", err),
+        };
+        let message = format!("{} timed out after {:?}.  Java threads at the time:\n\n{}", test, timeout, dump.trim_end());
+
+        for method in ["interrupt\0", "stop\0"].iter() {
+            if let Ok(guard) = guard.as_ref() { let _ = jni::call_void(guard.env(), java_thread, method, "()V\0", &[]); }
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(TIMEOUT_GRACE) { continue; }
+            return message;
+        }
+
+        eprintln!("{}\n\njerk: {} couldn't be interrupted or stopped, exiting the test process", message, test);
+        std::process::exit(101);
+    }
+}
+
+/// `Thread.interrupted()`, to clear the current thread's interrupt status after [Watchdog] interrupted it.
+unsafe fn clear_interrupted(env: *mut JNIEnv) -> std::io::Result<()> {
+    let thread_class    = jni::find_class(env, "java/lang/Thread\0")?;
+    let interrupted     = jni::static_method(env, thread_class, "interrupted\0", "()Z\0")?;
+    (**env).CallStaticBooleanMethodA.unwrap()(env, thread_class, interrupted, [].as_ptr());
+    jni::check(env)
+}
+
+/// Describe every Java thread's state, locks, and (full) stack trace, similar to `jstack`.
+///
+/// `ThreadInfo.toString()` truncates stacks to 8 frames, so this formats `ManagementFactory.getThreadMXBean().dumpAllThreads(true, true)` itself.
+unsafe fn thread_dump(env: *mut JNIEnv) -> std::io::Result<String> {
+    let _frame = jni::LocalFrame::push(env, 16)?;
+    let factory     = jni::find_class(env, "java/lang/management/ManagementFactory\0")?;
+    let get_bean    = jni::static_method(env, factory, "getThreadMXBean\0", "()Ljava/lang/management/ThreadMXBean;\0")?;
+    let bean        = (**env).CallStaticObjectMethodA.unwrap()(env, factory, get_bean, [].as_ptr());
+    let bean        = jni::non_null(env, bean, "ManagementFactory.getThreadMXBean()")?;
+    let infos       = jni::call_object(env, bean, "dumpAllThreads\0", "(ZZ)[Ljava/lang/management/ThreadInfo;\0", &[jvalue { z: JNI_TRUE }, jvalue { z: JNI_TRUE }])?;
+    let infos       = jni::non_null(env, infos, "ThreadMXBean.dumpAllThreads(...)")?;
+
+    let mut dump = String::new();
+    for i in 0 .. (**env).GetArrayLength.unwrap()(env, infos) {
+        let _frame  = jni::LocalFrame::push(env, 16)?;
+        let info    = (**env).GetObjectArrayElement.unwrap()(env, infos, i);
+        let info    = jni::non_null(env, info, "ThreadInfo")?;
+        let name    = jni::call_string(env, info, "getThreadName\0")?.unwrap_or_default();
+        let id      = jni::call_long(env, info, "getThreadId\0", "()J\0", &[])?;
+        let state   = jni::call_object(env, info, "getThreadState\0", "()Ljava/lang/Thread$State;\0", &[])?;
+        let state   = jni::to_string(env, state)?;
+        dump.push_str(&format!("{:?} #{} {}\n", name, id, state));
+
+        if let Some(lock) = jni::call_string(env, info, "getLockName\0")? {
+            dump.push_str(&format!("\t- waiting on <{}>", lock));
+            if let Some(owner) = jni::call_string(env, info, "getLockOwnerName\0")? {
+                let owner_id = jni::call_long(env, info, "getLockOwnerId\0", "()J\0", &[])?;
+                dump.push_str(&format!(" owned by {:?} #{}", owner, owner_id));
+            }
+            dump.push('\n');
+        }
+
+        let trace = jni::call_object(env, info, "getStackTrace\0", "()[Ljava/lang/StackTraceElement;\0", &[])?;
+        for frame in java_stack_trace(env, trace)? { dump.push_str(&format!("\tat {}\n", frame)); }
+        dump.push('\n');
+    }
+    Ok(dump)
+}
+
+/// The methods of `class` named `name`, as described by `java.lang.reflect.Method.toString()`
+unsafe fn declared_methods(env: *mut JNIEnv, class: jclass, name: &str) -> std::io::Result<Vec<String>> {
+    let _frame = jni::LocalFrame::push(env, 16)?;
+    let methods = jni::call_object(env, class, "getDeclaredMethods\0", "()[Ljava/lang/reflect/Method;\0", &[])?;
+    let methods = jni::non_null(env, methods, "Class.getDeclaredMethods()")?;
+    let mut found = Vec::new();
+    for i in 0 .. (**env).GetArrayLength.unwrap()(env, methods) {
+        let method = (**env).GetObjectArrayElement.unwrap()(env, methods, i);
+        let method = jni::non_null(env, method, "Method")?;
+        if jni::call_string(env, method, "getName\0")?.as_deref() == Some(name) { found.push(jni::to_string(env, method)?); }
+        (**env).DeleteLocalRef.unwrap()(env, method);
+    }
+    Ok(found)
+}
+
+/// Find `package.class` with `loader` if there is one, or `FindClass` otherwise.  Returns null (with no pending exception) if not found.
+unsafe fn find_test_class(env: *mut JNIEnv, loader: jobject, package: &str, class: &str) -> jclass {
+    let found = if loader.is_null() {
+        let class_id = format!("{}/{}\0", package.replace(".", "/"), class);
+        jni::find_class(env, &class_id)
+    } else {
+        let name = if package.is_empty() { class.to_string() } else { format!("{}.{}", package, class) };
+        jni::new_string(env, &name).and_then(|name| jni::call_object(env, loader, "loadClass\0", "(Ljava/lang/String;)Ljava/lang/Class;\0", &[jvalue { l: name }]))
+    };
+    found.unwrap_or(null_mut())
+}
+
+/// The class loader to load `package.class` with for the current test, according to [class_loader_scope].  Null means `FindClass`.
+unsafe fn test_class_loader(env: *mut JNIEnv, shared_loader: jobject, test: &str, classpath: &str, package: &str, class: &str) -> Result<jobject> {
+    close_class_loaders(env, std::mem::take(&mut *CLOSED_CLASS_LOADERS.lock().unwrap()));
+    match class_loader_scope()? {
+        TestClassLoader::PerTest => TEST_CLASS_LOADER.with(|loader| {
+            let mut loader = loader.borrow_mut();
+            // Compared by name, since libtest runs every #[test] on the main thread with --test-threads=1
+            if loader.as_ref().map_or(true, |(loader_test, _)| loader_test != test) {
+                if let Some((_, previous)) = loader.take() { close_class_loaders(env, previous.into_inner().into_iter().collect()); }
+                *loader = Some((test.to_string(), IsolatedClassLoader(Some(new_isolated_class_loader(env, shared_loader, classpath)?))));
+            }
+            Ok(loader.as_ref().unwrap().1.as_raw())
+        }),
+        TestClassLoader::PerClass => {
+            let mut loaders = CLASS_LOADERS.lock().unwrap();
+            let key = format!("{}.{}", package, class);
+            if !loaders.contains_key(&key) { loaders.insert(key.clone(), new_isolated_class_loader(env, shared_loader, classpath)?); }
+            Ok(loaders[&key].as_raw())
+        },
+        _shared => Ok(shared_loader),
+    }
+}
+
+/// [TestVmOptions::class_loader], unless overridden by `JERK_TEST_CLASS_LOADER`
+fn class_loader_scope() -> Result<TestClassLoader> {
+    match std::env::var("JERK_TEST_CLASS_LOADER").as_deref() {
+        Err(_) | Ok("") => Ok(VM_OPTIONS.lock().unwrap().class_loader),
+        Ok("shared")    => Ok(TestClassLoader::Shared),
+        Ok("test")      => Ok(TestClassLoader::PerTest),
+        Ok("class")     => Ok(TestClassLoader::PerClass),
+        Ok(other)       => Err(format!("JERK_TEST_CLASS_LOADER={:?} is invalid, expected \"shared\", \"test\", or \"class\"", other).into()),
+    }
+}
+
+/// `new com.maulingmonkey.jerk.IsolatedClassLoader(classpath)`, as a global reference.  `IsolatedClassLoader` itself is found through `shared_loader`.
+unsafe fn new_isolated_class_loader(env: *mut JNIEnv, shared_loader: jobject, classpath: &str) -> Result<jni::GlobalRef> {
+    let classpath = java_vm_classpath(classpath);
+    let _frame = jni::LocalFrame::push(env, 16 + 4 * classpath.len() as jint)?;
+    let class = find_test_class(env, shared_loader, "com.maulingmonkey.jerk", "IsolatedClassLoader");
+    if class.is_null() { return Err("com.maulingmonkey.jerk.IsolatedClassLoader not found on the test class path (is the crate built with jerk::metabuild()?)".into()); }
+    let urls = new_url_array(env, &classpath)?;
+    let loader = jni::new_object(env, class, "([Ljava/net/URL;)V\0", &[jvalue { l: urls }])?;
+
+    static AT_EXIT : std::sync::Once = std::sync::Once::new();
+    AT_EXIT.call_once(|| { atexit(close_class_loaders_at_exit); });
+    Ok(jni::GlobalRef::new(env, loader)?)
+}
+
+/// A class loader from [new_isolated_class_loader], queued for [close_class_loaders] when the `#[test]`'s thread exits.
+///
+/// It isn't closed by `drop` itself:  that runs from a thread-local destructor, when `ENV`'s destructor may have already
+/// detached the thread, and attaching a thread that's being torn down isn't something JNI promises to support.
+struct IsolatedClassLoader(Option<jni::GlobalRef>);
+impl IsolatedClassLoader {
+    fn as_raw(&self) -> jobject { self.0.as_ref().map_or(null_mut(), |loader| loader.as_raw()) }
+    fn into_inner(mut self) -> Option<jni::GlobalRef> { self.0.take() }
+}
+impl Drop for IsolatedClassLoader {
+    fn drop(&mut self) {
+        // Even if poisoned:  dropping the GlobalRef here would DeleteGlobalRef from the thread-local destructor
+        if let Some(loader) = self.0.take() { CLOSED_CLASS_LOADERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(loader); }
+    }
+}
+
+/// `URLClassLoader.close()` (which deletes its links to native libraries) and `DeleteGlobalRef` each of `loaders`.
+unsafe fn close_class_loaders(env: *mut JNIEnv, loaders: Vec<jni::GlobalRef>) {
+    for loader in loaders.into_iter() {
+        let _ = jni::call_void(env, loader.as_raw(), "close\0", "()V\0", &[]);
+        loader.delete(env);
+    }
+}
+
+/// Close every remaining isolated class loader at exit, so their links to native libraries don't accumulate next to the library.
+/// `TestClassLoader::PerClass` loaders live until then, and libtest exits without running the main thread's thread-local destructors.
+extern "C" fn close_class_loaders_at_exit() {
+    let vm = match VM.lock().map(|vm| *vm) { Ok(Some(vm)) => vm, _ => return };
+    let mut loaders = std::mem::take(&mut *CLOSED_CLASS_LOADERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    loaders.extend(std::mem::take(&mut *CLASS_LOADERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())).into_values());
+    if let Ok(Some((_, loader))) = TEST_CLASS_LOADER.try_with(|loader| loader.borrow_mut().take()) { loaders.extend(loader.into_inner()); }
+    let _ = vm.with_env(|env| unsafe { close_class_loaders(env, loaders) });
+}
+
+extern "C" { pub(crate) fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int; }
 
-lazy_static::lazy_static! { static ref JVM : jvm::Library = jvm::Library::get().unwrap(); }
+/// A local `java.net.URL[]` of `classpath`
+unsafe fn new_url_array(env: *mut JNIEnv, classpath: &[PathBuf]) -> std::io::Result<jobjectArray> {
+    let file_class  = jni::find_class(env, "java/io/File\0")?;
+    let url_class   = jni::find_class(env, "java/net/URL\0")?;
+    let urls        = (**env).NewObjectArray.unwrap()(env, classpath.len() as jsize, url_class, null_mut());
+    let urls        = jni::non_null(env, urls, "NewObjectArray")?;
+    for (i, path) in classpath.iter().enumerate() {
+        let path    = jni::new_string(env, &path.to_string_lossy())?;
+        let file    = jni::new_object(env, file_class, "(Ljava/lang/String;)V\0", &[jvalue { l: path }])?;
+        let uri     = jni::call_object(env, file, "toURI\0", "()Ljava/net/URI;\0", &[])?;
+        let url     = jni::call_object(env, uri, "toURL\0", "()Ljava/net/URL;\0", &[])?;
+        (**env).SetObjectArrayElement.unwrap()(env, urls, i as jsize, url);
+        jni::check(env)?;
+        (**env).DeleteLocalRef.unwrap()(env, url);
+        (**env).DeleteLocalRef.unwrap()(env, uri);
+        (**env).DeleteLocalRef.unwrap()(env, file);
+        (**env).DeleteLocalRef.unwrap()(env, path);
+    }
+    Ok(urls)
+}
+
+/// `new URLClassLoader(classpath, ClassLoader.getSystemClassLoader())`, as a global reference.
+fn new_class_loader(vm: &jvm::Vm, classpath: &[PathBuf]) -> std::io::Result<jni::GlobalRef> {
+    vm.with_env(|env| unsafe { new_class_loader_with(env, classpath) })
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, format!("unable to attach to the Java VM: {}", err)))?
+}
+
+unsafe fn new_class_loader_with(env: *mut JNIEnv, classpath: &[PathBuf]) -> std::io::Result<jni::GlobalRef> {
+    let _frame = jni::LocalFrame::push(env, 16)?;
+    let urls = new_url_array(env, classpath)?;
+
+    let class_loader_class  = jni::find_class(env, "java/lang/ClassLoader\0")?;
+    let get_system_loader   = jni::static_method(env, class_loader_class, "getSystemClassLoader\0", "()Ljava/lang/ClassLoader;\0")?;
+    let parent              = (**env).CallStaticObjectMethodA.unwrap()(env, class_loader_class, get_system_loader, [].as_ptr());
+    jni::check(env)?;
+
+    let url_class_loader_class  = jni::find_class(env, "java/net/URLClassLoader\0")?;
+    let loader = jni::new_object(env, url_class_loader_class, "([Ljava/net/URL;Ljava/lang/ClassLoader;)V\0", &[jvalue { l: urls }, jvalue { l: parent }])?;
+    jni::GlobalRef::new(env, loader)
+}
+
+/// Redirect `System.out` and `System.err` through `com.maulingmonkey.jerk.TestOutputStream` to [print!] and [eprint!].
+/// Does nothing if `TestOutputStream` isn't on the class path (e.g. the crate isn't built with `jerk::metabuild()`.)
+unsafe fn capture_output(env: *mut JNIEnv) -> std::io::Result<()> {
+    let _frame = jni::LocalFrame::push(env, 16)?;
+    let class = match jni::find_class(env, "com/maulingmonkey/jerk/TestOutputStream\0") {
+        Ok(class)   => class,
+        Err(_)      => return Ok(()),
+    };
+    let methods = [JNINativeMethod {
+        name:       "write\0".as_ptr() as *mut _,
+        signature:  "(I[BII)V\0".as_ptr() as *mut _,
+        fnPtr:      test_output_write as *mut _,
+    }];
+    if (**env).RegisterNatives.unwrap()(env, class, methods.as_ptr(), methods.len() as jint) != JNI_OK { jni::check(env)?; }
+    let install = jni::static_method(env, class, "install\0", "()V\0")?;
+    (**env).CallStaticVoidMethodA.unwrap()(env, class, install, [].as_ptr());
+    jni::check(env)
+}
+
+/// `com.maulingmonkey.jerk.TestOutputStream.write(int fd, byte[] b, int off, int len)`
+unsafe extern "system" fn test_output_write(env: *mut JNIEnv, _class: jclass, fd: jint, b: jbyteArray, off: jint, len: jint) {
+    let mut bytes = vec![0 as jbyte; len.max(0) as usize];
+    (**env).GetByteArrayRegion.unwrap()(env, b, off, len, bytes.as_mut_ptr());
+    if (**env).ExceptionCheck.unwrap()(env) == JNI_TRUE { return; }
+
+    // A panic can't unwind into Java (UB), so is caught here:  PARTIAL_OUTPUT may be gone if the thread is exiting, etc.
+    let _ = std::panic::catch_unwind(|| PARTIAL_OUTPUT.with(|partial| {
+        let mut partial = partial.borrow_mut();
+        let partial = &mut partial[if fd == 2 { 1 } else { 0 }];
+        partial.extend(bytes.iter().map(|&b| b as u8));
+        // Hold back a UTF-8 sequence that was split across writes
+        let complete = match std::str::from_utf8(partial) {
+            Err(err) if err.error_len().is_none()   => err.valid_up_to(),
+            _                                       => partial.len(),
+        };
+        let text = String::from_utf8_lossy(&partial[..complete]).into_owned();
+        partial.drain(..complete);
+
+        // print!/eprint! are what libtest captures, but panic on write errors (e.g. EPIPE)
+        let printed = std::panic::catch_unwind(|| if fd == 2 { eprint!("{}", text); } else { print!("{}", text); });
+        if printed.is_err() {
+            let _ = if fd == 2 { std::io::stderr().write_all(text.as_bytes()) } else { std::io::stdout().write_all(text.as_bytes()) };
+        }
+    }));
+}
+// Incomplete UTF-8 sequences at the end of the last `System.out`, `System.err` write on this thread
+thread_local! { static PARTIAL_OUTPUT : RefCell<[Vec<u8>; 2]> = RefCell::new([Vec::new(), Vec::new()]); }
+
+lazy_static::lazy_static! { static ref JVM : std::result::Result<jvm::Library, String> = jvm::Library::get().map_err(|err| err.to_string()); }
 
 /// Get a handle to the current Java VM, or create one if it doesn't already exist.
 pub fn test_vm() -> *mut JavaVM {
-    let vm = **VM.lock().unwrap();
-    debug_assert!(!vm.is_null(), "VM is null, are you trying to access the test_vm outside of a `run_test!`?");
-    vm
+    let vm = vm();
+    debug_assert!(vm.is_some(), "VM is null, are you trying to access the test_vm outside of a `run_test!`?");
+    vm.map_or(null_mut(), |vm| vm.as_raw())
+}
+
+/// The test Java VM, if a [run_test!] has created (or found) it yet.
+pub fn vm() -> Option<&'static jvm::Vm> {
+    *VM.lock().unwrap()
 }
-lazy_static::lazy_static! { static ref VM : Mutex<ThreadSafe<*mut JavaVM>> = Mutex::new(ThreadSafe(null_mut())); }
+lazy_static::lazy_static! { static ref VM : Mutex<Option<&'static jvm::Vm>> = Mutex::new(None); }
+// The `URLClassLoader` test classes are loaded through, if VM was reused instead of created by jerk.
+lazy_static::lazy_static! { static ref CLASS_LOADER : Mutex<Option<jni::GlobalRef>> = Mutex::new(None); }
+// TestClassLoader::PerClass class loaders, by `package.class`
+lazy_static::lazy_static! { static ref CLASS_LOADERS : Mutex<BTreeMap<String, jni::GlobalRef>> = Mutex::new(BTreeMap::new()); }
+// The TestClassLoader::PerTest class loader of the current #[test], and the #[test]'s name
+thread_local! { static TEST_CLASS_LOADER : RefCell<Option<(String, IsolatedClassLoader)>> = RefCell::new(None); }
+// Global references to isolated class loaders whose tests have finished, to close from a thread that's still attached
+lazy_static::lazy_static! { static ref CLOSED_CLASS_LOADERS : Mutex<Vec<jni::GlobalRef>> = Mutex::new(Vec::new()); }
 
 /// Get a handle to the Java environment for the current thread, attaching if one doesn't already exist.
-pub fn test_thread_env() -> *mut JNIEnv { ENV.with(|e| *e) }
-thread_local! { static ENV : *mut JNIEnv = attach_current_thread(); }
-
-fn attach_current_thread() -> *mut JNIEnv {
-    let vm = test_vm();
-    let mut env = null_mut();
-    assert_eq!(JNI_OK, unsafe { (**vm).AttachCurrentThread.unwrap()(vm, &mut env, null_mut()) });
-    env as *mut _
-}
-
-fn create_java_vm(jar: &str, reused_vm: &mut bool) -> *mut JavaVM {
-    match JVM.create_java_vm(vec![
-        //"-verbose:class".to_string(),
-        //"-verbose:jni".to_string(),
-        "-ea".to_string(),  // Enable Assertions
-        "-esa".to_string(), // Enable System Assertions
-        format!("-Djava.class.path={}", jar),
-    ]) {
+///
+/// Threads attached by this are daemon threads named after the current `#[test]`, and are detached when they exit.
+pub fn test_thread_env() -> *mut JNIEnv {
+    ENV.with(|guard| {
+        let mut guard = guard.borrow_mut();
+        if !guard.as_ref().map_or(false, |guard| guard.is_attached()) { *guard = Some(attach_current_thread()); }
+        guard.as_ref().unwrap().env()
+    })
+}
+thread_local! { static ENV : RefCell<Option<jvm::AttachGuard<'static>>> = RefCell::new(None); }
+
+fn attach_current_thread() -> jvm::AttachGuard<'static> {
+    let name = std::thread::current().name().map(String::from);
+    let vm = vm().expect("Unable to attach the current thread to the test Java VM: it hasn't been created yet");
+    vm.attach_current_thread_as_daemon(name.as_deref()).unwrap_or_else(|err| panic!("Unable to attach the current thread to the test Java VM: {}", err))
+}
+
+/// Call `f` with the current thread's Java environment for the test Java VM, which must already exist (see [vm]).
+///
+/// Reuses the current thread's attachment if there is one, otherwise attaches only for the duration of `f`.  Useful for
+/// checking Java state from your own worker threads, without leaving them attached:
+///
+/// ```no_run
+/// std::thread::spawn(|| jerk::test::with_env(|env| {
+///     // ...
+/// }).unwrap()).join().unwrap();
+/// ```
+pub fn with_env<R>(f: impl FnOnce(*mut JNIEnv) -> R) -> Result<R> {
+    let vm = vm().ok_or("jerk::test::with_env: the test Java VM hasn't been created yet")?;
+    vm.with_env(f).map_err(|err| format!("jerk::test::with_env: unable to attach to the test Java VM: {}", err).into())
+}
+
+/// Options for the Java VM that [run_test!] creates.  See [configure].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TestVmOptions {
+    /// `-ea` - enable assertions in non-system classes (default `true`)
+    pub assertions:             bool,
+    /// `-esa` - enable assertions in system classes (default `true`)
+    pub system_assertions:      bool,
+    /// `-ea:...` - enable assertions for specific packages (`"com.example..."`) or classes (`"com.example.Foo"`)
+    pub enable_assertions:      Vec<String>,
+    /// `-da:...` - disable assertions for specific packages (`"com.example..."`) or classes (`"com.example.Foo"`)
+    pub disable_assertions:     Vec<String>,
+    /// `-Xms...` - e.g. `"64m"`
+    pub initial_heap:           Option<String>,
+    /// `-Xmx...` - e.g. `"1g"`
+    pub max_heap:               Option<String>,
+    /// `-Xcheck:jni` - validate JNI calls, at some cost in speed.  Highly recommended when testing native methods.
+    pub check_jni:              bool,
+    /// `-verbose:...` - e.g. `"jni"`, `"class"`, `"gc"`
+    pub verbose:                Vec<String>,
+    /// `-Dkey=value`
+    pub system_properties:      Vec<(String, String)>,
+    /// Appended to the `java.class.path`, after the crate's own `.jar`
+    pub class_paths:            Vec<PathBuf>,
+    /// Any other options, passed to `JNI_CreateJavaVM` as-is (except `-Djava.class.path=...`, which is appended to [class_paths](#structfield.class_paths))
+    pub options:                Vec<String>,
+    /// Which class loader(s) test classes are loaded with.  Overridden by `JERK_TEST_CLASS_LOADER` (`shared`, `test`, or `class`) if set.
+    pub class_loader:           TestClassLoader,
+    /// Forward `System.out` and `System.err` to [print!] and [eprint!] on the calling thread (default `true`), so libtest captures
+    /// Java output along with the rest of the test's output, and `--nocapture` shows it.  Not applied to a reused Java VM.
+    pub capture_output:         bool,
+
+    #[doc(hidden)] pub _non_exhaustive: (),
+}
+
+impl Default for TestVmOptions {
+    fn default() -> Self {
+        Self {
+            assertions:             true,
+            system_assertions:      true,
+            enable_assertions:      Vec::new(),
+            disable_assertions:     Vec::new(),
+            initial_heap:           None,
+            max_heap:               None,
+            check_jni:              false,
+            verbose:                Vec::new(),
+            system_properties:      Vec::new(),
+            class_paths:            Vec::new(),
+            options:                Vec::new(),
+            class_loader:           TestClassLoader::Shared,
+            capture_output:         true,
+            _non_exhaustive:        (),
+        }
+    }
+}
+
+/// Which class loader(s) [run_test!] loads test classes with.  See [TestVmOptions::class_loader].
+///
+/// Fresh class loaders load the crate's classes from the test class path again, so their Java statics start out freshly initialized.
+/// A native library can only be loaded into one class loader, so `System.loadLibrary` in a fresh class loader loads a temporary
+/// hard link to the library instead.  On Linux and macOS that's the same library as the rest of the test process uses, but Windows
+/// loads a separate copy with separate Rust statics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TestClassLoader {
+    /// All tests share one class loader, and thus the same Java static state (the default)
+    Shared,
+    /// Each `#[test]` gets a fresh class loader, shared by all `run_test!`s within it
+    PerTest,
+    /// Each Java class `run_test!` is called on gets a fresh class loader, shared by all tests of that class
+    PerClass,
+    #[doc(hidden)] _NonExhaustive,
+}
+
+impl Default for TestClassLoader {
+    fn default() -> Self { TestClassLoader::Shared }
+}
+
+lazy_static::lazy_static! { static ref VM_OPTIONS : Mutex<TestVmOptions> = Mutex::new(TestVmOptions::default()); }
+
+/// Configure the Java VM that [run_test!] creates.  Must be called before the VM is first created.
+///
+/// libtest runs `#[test]`s in parallel and in no particular order, so call this at the start of every test that depends on it.
+/// Calling it again with the same options after the VM exists is fine, but different options are an error.
+///
+/// Options from the `JERK_TEST_JVM_OPTS` environment variable (whitespace separated, e.g. `-Xcheck:jni -Dfoo=bar`) are applied after these.
+/// `-Djava.class.path=...` there is appended to the class path instead of replacing it.
+///
+/// ```no_run
+/// jerk::test::configure(jerk::test::TestVmOptions {
+///     check_jni:          true,
+///     max_heap:           Some("256m".into()),
+///     system_properties:  vec![("com.example.mode".into(), "test".into())],
+///     ..Default::default()
+/// }).unwrap();
+/// ```
+pub fn configure(options: TestVmOptions) -> Result<()> {
+    let vm = VM.lock().unwrap();
+    let mut current = VM_OPTIONS.lock().unwrap();
+    if vm.is_none() {
+        *current = options;
+        Ok(())
+    } else if *current == options {
+        Ok(())
+    } else {
+        Err("jerk::test::configure(...) called with different options after the test Java VM was already created".into())
+    }
+}
+
+/// The full test class path: `classpath` + [TestVmOptions::class_paths] + any `-Djava.class.path=...` in [TestVmOptions::options] or `JERK_TEST_JVM_OPTS`
+fn java_vm_classpath(classpath: &str) -> Vec<PathBuf> {
+    let options = VM_OPTIONS.lock().unwrap().clone();
+    let mut classpath = std::env::split_paths(classpath).chain(options.class_paths.iter().cloned()).collect::<Vec<_>>();
+    let env = std::env::var_os("JERK_TEST_JVM_OPTS").map(|env| env.to_string_lossy().into_owned()).unwrap_or_default();
+    for option in options.options.iter().map(|option| &option[..]).chain(env.split_whitespace()) {
+        if let Some(paths) = option.strip_prefix("-Djava.class.path=") { classpath.extend(std::env::split_paths(paths)); }
+    }
+    classpath
+}
+
+/// The `JNI_CreateJavaVM` arguments for [VM_OPTIONS] + `JERK_TEST_JVM_OPTS`
+fn java_vm_init_args(classpath: &str) -> jvm::InitArgs {
+    let class_paths = java_vm_classpath(classpath);
+    let options = VM_OPTIONS.lock().unwrap().clone();
+    let mut args = Vec::new();
+
+    if options.assertions           { args.push("-ea".to_string()); }
+    if options.system_assertions    { args.push("-esa".to_string()); }
+    for target in options.enable_assertions.iter()  { args.push(format!("-ea:{}", target)); }
+    for target in options.disable_assertions.iter() { args.push(format!("-da:{}", target)); }
+    for what in options.verbose.iter()              { args.push(format!("-verbose:{}", what)); }
+    // `-Djava.class.path=...`s were merged into `class_paths` by java_vm_classpath, and would otherwise replace the crate's own `.jar`
+    args.extend(options.options.iter().filter(|option| !option.starts_with("-Djava.class.path=")).cloned());
+
+    if let Some(env) = std::env::var_os("JERK_TEST_JVM_OPTS") {
+        args.extend(env.to_string_lossy().split_whitespace().filter(|option| !option.starts_with("-Djava.class.path=")).map(String::from));
+    }
+
+    jvm::InitArgs {
+        class_paths,
+        system_properties:  options.system_properties,
+        initial_heap:       options.initial_heap,
+        max_heap:           options.max_heap,
+        check_jni:          options.check_jni,
+        options:            args,
+        ..jvm::InitArgs::default()
+    }
+}
+
+fn create_java_vm(classpath: &str, reused_vm: &mut bool) -> Result<jvm::Vm> {
+    let jvm = JVM.as_ref().map_err(|err| JavaTestError::VmCreation(format!("Unable to load the Java VM library: {}", err)))?;
+
+    // Check before creating:  on some JDKs, a JNI_CreateJavaVM that fails with JNI_EEXIST leaves JNI_GetCreatedJavaVMs returning nothing
+    // Safety:  jerk never destroys the test Java VM, and a Java VM that existed before the tests is expected to outlive them.
+    if let Some(vm) = unsafe { jvm.get_created_vms() }.ok().and_then(|vms| vms.into_iter().next()) {
+        *reused_vm = true;
+        return Ok(vm);
+    }
+
+    match jvm.create_vm_with(&java_vm_init_args(classpath)) {
         Err(JniError::EXIST) => {
-            if let Some(vm) = JVM.get_created_java_vms().unwrap().into_iter().next() {
+            if let Some(vm) = unsafe { jvm.get_created_vms() }.ok().and_then(|vms| vms.into_iter().next()) {
                 *reused_vm = true;
-                vm
+                Ok(vm)
             } else {
-                panic!("JNI_EEXIST error creating Java VM, but unable to get an existing VM");
+                Err(JavaTestError::VmCreation("JNI_EEXIST error creating Java VM, but unable to get an existing VM".into()))
             }
         },
-        Err(err) => panic!("JNI error creating Java VM: {}", err),
-        Ok(vm) => vm
+        Err(JniError::INVAL) => Err(JavaTestError::VmCreation("JNI_EINVAL error creating Java VM: are there any nul characters or invalid paths in the test Java VM options?".into())),
+        Err(err) => Err(JavaTestError::VmCreation(format!("JNI error creating Java VM: {}", err))),
+        Ok(vm) => Ok(vm)
     }
-
 }
-
-struct ThreadSafe<T>(pub T);
-impl<T> std::ops::Deref for ThreadSafe<T> { type Target = T; fn deref(&self) -> &Self::Target { &self.0 } }
-impl<T> std::ops::DerefMut for ThreadSafe<T> { fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 } }
-unsafe impl<T> Send for ThreadSafe<T> {}
-unsafe impl<T> Sync for ThreadSafe<T> {}
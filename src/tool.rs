@@ -0,0 +1,22 @@
+//! Crate internal helpers shared by the `%JAVA_HOME%\bin\*` tool wrappers
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Command line arguments, for `Command::args` or an `@argfile`
+pub(crate) struct Args(pub Vec<OsString>);
+
+impl Args {
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.0.push(arg.as_ref().to_owned());
+        self
+    }
+}
+
+/// `java_home` if specified, otherwise [find_java_home](crate::search::find_java_home)
+pub(crate) fn java_home(java_home: Option<&Path>) -> io::Result<PathBuf> {
+    java_home.map(PathBuf::from).or_else(crate::search::find_java_home).ok_or_else(||
+        Error::new(ErrorKind::NotFound, "JAVA_HOME not set and could not be found, unable to run")
+    )
+}
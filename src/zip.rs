@@ -0,0 +1,433 @@
+//! Minimal `.zip` format support for `.jar`s
+//!
+//! No zip64, encryption, or multi-disk archives.  Reference: <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Error, ErrorKind, Write};
+use std::path::Path;
+
+const LOCAL_FILE_HEADER_SIGNATURE   : u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_SIGNATURE   : u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR_SIGNATURE  : u32 = 0x06054b50;
+
+const FLAG_ENCRYPTED        : u16 = 1 << 0;
+const FLAG_UTF8             : u16 = 1 << 11;
+const VERSION_STORED        : u16 = 10;
+const VERSION_DEFLATED      : u16 = 20;
+const ATTRIBUTE_DIRECTORY   : u32 = 0x10; // MS-DOS FILE_ATTRIBUTE_DIRECTORY
+const EXTRA_TIMESTAMP       : u16 = 0x5455; // "UT" extended timestamp:  flags, then UTC Unix times (just the modification time in the central directory)
+
+/// Compression method of a zip entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Method {
+    Stored      = 0,
+    Deflated    = 8,
+}
+
+/// MS-DOS style timestamp, as stored in zip headers (2 second resolution, no timezone, 1980 ..= 2107)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct DosDateTime {
+    pub date: u16,
+    pub time: u16,
+}
+
+impl DosDateTime {
+    /// 1980-01-01 00:00:00
+    pub const MIN : DosDateTime = DosDateTime { date: (1 << 5) | 1, time: 0 };
+
+    /// Convert seconds since the Unix epoch (UTC), clamping to the representable range.
+    pub fn from_unix(secs: u64) -> Self {
+        let days = secs / 86400;
+        let secs = secs % 86400;
+        let (year, month, day) = civil_from_days(days);
+        if year < 1980 { return Self::MIN; }
+        if year > 2107 { return Self { date: (127 << 9) | (12 << 5) | 31, time: (23 << 11) | (59 << 5) | 29 }; }
+        Self {
+            date: (((year - 1980) as u16) << 9) | ((month as u16) << 5) | (day as u16),
+            time: (((secs / 3600) as u16) << 11) | ((((secs / 60) % 60) as u16) << 5) | (((secs % 60) / 2) as u16),
+        }
+    }
+
+    /// Convert to seconds since the Unix epoch, treating the timestamp as UTC.  That's what [from_unix](#method.from_unix) writes,
+    /// but most other zip tools (including `bin/jar`) write local time, so their timestamps will be off by the writer's UTC offset.
+    pub fn to_unix(self) -> u64 {
+        let year    = 1980 + (self.date >> 9) as u64;
+        let month   = ((self.date >> 5) & 0xF).max(1) as u64;
+        let day     = (self.date & 0x1F).max(1) as u64;
+        let secs    = (self.time >> 11) as u64 * 3600 + ((self.time >> 5) & 0x3F) as u64 * 60 + (self.time & 0x1F) as u64 * 2;
+        days_from_civil(year, month, day) * 86400 + secs
+    }
+}
+
+/// (year, month 1..=12, day 1..=31) => days since 1970-01-01.  See <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2)/5 + day - 1;
+    let doe = yoe * 365 + yoe/4 - yoe/100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Days since 1970-01-01 => (year, month 1..=12, day 1..=31).  See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2) / 153;
+    let day = doy - (153*mp + 2)/5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// CRC-32 (IEEE 802.3 polynomial, as used by zip)
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    lazy_static::lazy_static! {
+        static ref TABLE : [u32; 256] = {
+            let mut table = [0u32; 256];
+            for (i, entry) in table.iter_mut().enumerate() {
+                let mut c = i as u32;
+                for _ in 0..8 { c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 }; }
+                *entry = c;
+            }
+            table
+        };
+    }
+
+    !data.iter().fold(!0u32, |crc, &b| TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+/// Writes a `.zip` to `out`, entry by entry, in the order given.
+pub(crate) struct Writer<W: Write> {
+    out:        W,
+    offset:     u32,
+    entries:    u16,
+    central:    Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, offset: 0, entries: 0, central: Vec::new() }
+    }
+
+    /// Add a directory entry.  `name` should end with `/`.
+    pub fn directory(&mut self, name: &str, modified: DosDateTime, extra: &[u8]) -> io::Result<()> {
+        debug_assert!(name.ends_with('/'));
+        self.entry(name, Method::Stored, &[], 0, 0, modified, extra, ATTRIBUTE_DIRECTORY)
+    }
+
+    /// Add a file entry, compressing `data` with `method`.
+    pub fn file(&mut self, name: &str, method: Method, data: &[u8], modified: DosDateTime) -> io::Result<()> {
+        let crc = crc32(data);
+        match method {
+            Method::Stored      => self.entry(name, method, data, data.len(), crc, modified, &[], 0),
+            Method::Deflated    => self.entry(name, method, &miniz_oxide::deflate::compress_to_vec(data, 6), data.len(), crc, modified, &[], 0),
+        }
+    }
+
+    /// Copy an entry read by [Reader] as-is, without recompressing it.
+    pub fn copy(&mut self, reader: &Reader, entry: &Entry) -> io::Result<()> {
+        let method = match entry.method { 0 => Method::Stored, 8 => Method::Deflated, other => return Err(unsupported_method(&entry.name, other)) };
+        self.entry(&entry.name, method, reader.raw_data(entry)?, entry.uncompressed as usize, entry.crc, entry.modified, &entry.extra, entry.attributes)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn entry(&mut self, name: &str, method: Method, data: &[u8], uncompressed: usize, crc: u32, modified: DosDateTime, extra: &[u8], attributes: u32) -> io::Result<()> {
+        let version     = if method == Method::Stored { VERSION_STORED } else { VERSION_DEFLATED };
+        let name_len    = u16::try_from(name.len()).map_err(|_| too_large("entry name"))?;
+        let extra_len   = u16::try_from(extra.len()).map_err(|_| too_large("extra field"))?;
+        let compressed  = u32::try_from(data.len()).map_err(|_| too_large(name))?;
+        let uncompressed= u32::try_from(uncompressed).map_err(|_| too_large(name))?;
+        self.entries    = self.entries.checked_add(1).ok_or_else(|| too_large("number of entries"))?;
+
+        let mut local = Vec::with_capacity(30 + name.len() + extra.len());
+        put32(&mut local, LOCAL_FILE_HEADER_SIGNATURE);
+        put16(&mut local, version);
+        put16(&mut local, FLAG_UTF8);
+        put16(&mut local, method as u16);
+        put16(&mut local, modified.time);
+        put16(&mut local, modified.date);
+        put32(&mut local, crc);
+        put32(&mut local, compressed);
+        put32(&mut local, uncompressed);
+        put16(&mut local, name_len);
+        put16(&mut local, extra_len);
+        local.extend_from_slice(name.as_bytes());
+        local.extend_from_slice(extra);
+
+        let c = &mut self.central;
+        put32(c, CENTRAL_DIRECTORY_SIGNATURE);
+        put16(c, version); // version made by (MS-DOS)
+        put16(c, version);
+        put16(c, FLAG_UTF8);
+        put16(c, method as u16);
+        put16(c, modified.time);
+        put16(c, modified.date);
+        put32(c, crc);
+        put32(c, compressed);
+        put32(c, uncompressed);
+        put16(c, name_len);
+        put16(c, extra_len);
+        put16(c, 0); // comment length
+        put16(c, 0); // disk number start
+        put16(c, 0); // internal attributes
+        put32(c, attributes);
+        put32(c, self.offset);
+        c.extend_from_slice(name.as_bytes());
+        c.extend_from_slice(extra);
+
+        self.out.write_all(&local)?;
+        self.out.write_all(data)?;
+        self.offset = u32::try_from(local.len()).ok()
+            .and_then(|local| self.offset.checked_add(local))
+            .and_then(|offset| offset.checked_add(compressed))
+            .ok_or_else(|| too_large("archive"))?;
+        Ok(())
+    }
+
+    /// Write the central directory and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let central_len = u32::try_from(self.central.len()).map_err(|_| too_large("central directory"))?;
+        let mut end = Vec::with_capacity(22);
+        put32(&mut end, END_OF_CENTRAL_DIR_SIGNATURE);
+        put16(&mut end, 0); // number of this disk
+        put16(&mut end, 0); // disk where central directory starts
+        put16(&mut end, self.entries);
+        put16(&mut end, self.entries);
+        put32(&mut end, central_len);
+        put32(&mut end, self.offset);
+        put16(&mut end, 0); // comment length
+
+        self.out.write_all(&self.central)?;
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// An entry from a `.zip`'s central directory
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub name:           String,
+    pub flags:          u16,
+    pub method:         u16,
+    pub modified:       DosDateTime,
+    pub crc:            u32,
+    pub compressed:     u32,
+    pub uncompressed:   u32,
+    pub extra:          Vec<u8>,
+    pub attributes:     u32,
+    pub offset:         u32,
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool { self.name.ends_with('/') }
+
+    /// Seconds since the Unix epoch:  the extended timestamp extra field if present (UTC), otherwise [modified](#structfield.modified) as UTC.
+    pub fn modified_unix(&self) -> u64 {
+        let mut extra = &self.extra[..];
+        while let (Some(id), Some(len)) = (get16(extra, 0), get16(extra, 2)) {
+            let data = match extra.get(4 .. 4 + len as usize) { Some(data) => data, None => break };
+            if id == EXTRA_TIMESTAMP && data.first().copied().unwrap_or(0) & 1 != 0 {
+                if let Some(mtime) = get32(data, 1) { return (mtime as i32).max(0) as u64; }
+            }
+            extra = &extra[4 + len as usize ..];
+        }
+        self.modified.to_unix()
+    }
+}
+
+/// Reads a `.zip` held entirely in memory.
+pub(crate) struct Reader {
+    data:       Vec<u8>,
+    entries:    Vec<Entry>,
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(fs::read(path)?).map_err(|err| Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn new(data: Vec<u8>) -> io::Result<Self> {
+        // The end of central directory record is 22 bytes, followed by a comment of up to 64 KiB.
+        let min = data.len().saturating_sub(22 + 0xFFFF);
+        let end = (min ..= data.len().saturating_sub(22)).rev()
+            .find(|&i| get32(&data, i) == Some(END_OF_CENTRAL_DIR_SIGNATURE))
+            .ok_or_else(|| invalid("end of central directory not found"))?;
+
+        let count       = get16(&data, end + 10).ok_or_else(|| invalid("truncated end of central directory"))?;
+        let mut pos     = get32(&data, end + 16).ok_or_else(|| invalid("truncated end of central directory"))? as usize;
+        if count == 0xFFFF || pos == 0xFFFF_FFFF { return Err(Error::new(ErrorKind::Unsupported, "zip64 archives are not supported")); }
+
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0 .. count {
+            let truncated = || invalid("truncated central directory");
+            if get32(&data, pos) != Some(CENTRAL_DIRECTORY_SIGNATURE) { return Err(invalid("bad central directory signature")); }
+            let name_len    = get16(&data, pos + 28).ok_or_else(truncated)? as usize;
+            let extra_len   = get16(&data, pos + 30).ok_or_else(truncated)? as usize;
+            let comment_len = get16(&data, pos + 32).ok_or_else(truncated)? as usize;
+            let name        = data.get(pos + 46 .. pos + 46 + name_len).ok_or_else(truncated)?;
+            let extra       = data.get(pos + 46 + name_len .. pos + 46 + name_len + extra_len).ok_or_else(truncated)?;
+            entries.push(Entry {
+                name:           String::from_utf8(name.to_vec()).map_err(|_| invalid("entry name is not valid UTF-8"))?,
+                flags:          get16(&data, pos +  8).ok_or_else(truncated)?,
+                method:         get16(&data, pos + 10).ok_or_else(truncated)?,
+                modified:       DosDateTime { time: get16(&data, pos + 12).ok_or_else(truncated)?, date: get16(&data, pos + 14).ok_or_else(truncated)? },
+                crc:            get32(&data, pos + 16).ok_or_else(truncated)?,
+                compressed:     get32(&data, pos + 20).ok_or_else(truncated)?,
+                uncompressed:   get32(&data, pos + 24).ok_or_else(truncated)?,
+                extra:          extra.to_vec(),
+                attributes:     get32(&data, pos + 38).ok_or_else(truncated)?,
+                offset:         get32(&data, pos + 42).ok_or_else(truncated)?,
+            });
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+
+        Ok(Self { data, entries })
+    }
+
+    pub fn entries(&self) -> &[Entry] { &self.entries }
+
+    pub fn find(&self, name: &str) -> Option<&Entry> { self.entries.iter().find(|e| e.name == name) }
+
+    /// The still compressed data of `entry`
+    pub fn raw_data(&self, entry: &Entry) -> io::Result<&[u8]> {
+        let pos = entry.offset as usize;
+        let truncated = || invalid(&format!("truncated local header for {}", entry.name));
+        if get32(&self.data, pos) != Some(LOCAL_FILE_HEADER_SIGNATURE) { return Err(invalid(&format!("bad local header signature for {}", entry.name))); }
+        let name_len    = get16(&self.data, pos + 26).ok_or_else(truncated)? as usize;
+        let extra_len   = get16(&self.data, pos + 28).ok_or_else(truncated)? as usize;
+        let start       = pos + 30 + name_len + extra_len;
+        self.data.get(start .. start + entry.compressed as usize).ok_or_else(truncated)
+    }
+
+    /// The decompressed data of `entry`, with its CRC verified
+    pub fn data(&self, entry: &Entry) -> io::Result<Vec<u8>> {
+        if entry.flags & FLAG_ENCRYPTED != 0 { return Err(Error::new(ErrorKind::Unsupported, format!("{} is encrypted", entry.name))); }
+        let raw = self.raw_data(entry)?;
+        let data = match entry.method {
+            0 => raw.to_vec(),
+            8 => miniz_oxide::inflate::decompress_to_vec(raw).map_err(|err| invalid(&format!("error inflating {}: {:?}", entry.name, err)))?,
+            other => return Err(unsupported_method(&entry.name, other)),
+        };
+        if data.len() != entry.uncompressed as usize || crc32(&data) != entry.crc {
+            return Err(invalid(&format!("{} is corrupt (size or CRC mismatch)", entry.name)));
+        }
+        Ok(data)
+    }
+}
+
+fn get16(data: &[u8], pos: usize) -> Option<u16> { let b = data.get(pos .. pos + 2)?; Some(u16::from_le_bytes([b[0], b[1]])) }
+fn get32(data: &[u8], pos: usize) -> Option<u32> { let b = data.get(pos .. pos + 4)?; Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]])) }
+
+fn put16(out: &mut Vec<u8>, value: u16) { out.extend_from_slice(&value.to_le_bytes()); }
+fn put32(out: &mut Vec<u8>, value: u32) { out.extend_from_slice(&value.to_le_bytes()); }
+
+fn invalid(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, what.to_string())
+}
+
+fn unsupported_method(name: &str, method: u16) -> Error {
+    Error::new(ErrorKind::Unsupported, format!("{} uses unsupported compression method {}", name, method))
+}
+
+fn too_large(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("{} too large for a non-zip64 archive", what))
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn write(method: Method, modified: DosDateTime) -> Vec<u8> {
+        let mut zip = Writer::new(Vec::new());
+        zip.directory("META-INF/", modified, &[0xFE, 0xCA, 0x00, 0x00]).unwrap();
+        zip.file("META-INF/MANIFEST.MF", method, b"Manifest-Version: 1.0\r\n\r\n", modified).unwrap();
+        zip.file("com/example/Empty.class", method, b"", modified).unwrap();
+        zip.file("com/example/Repeated.txt", method, "jerk ".repeat(1000).as_bytes(), modified).unwrap();
+        zip.finish().unwrap()
+    }
+
+    #[test] fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test] fn dos_date_time() {
+        assert_eq!(DosDateTime::from_unix(0), DosDateTime::MIN);
+        assert_eq!(DosDateTime::MIN.to_unix(), 315532800);
+        let t = 1_600_000_000; // 2020-09-13 12:26:40 UTC
+        assert_eq!(DosDateTime::from_unix(t), DosDateTime { date: (40 << 9) | (9 << 5) | 13, time: (12 << 11) | (26 << 5) | (40 / 2) });
+        assert_eq!(DosDateTime::from_unix(t).to_unix(), t);
+        assert_eq!(DosDateTime::from_unix(t + 1).to_unix(), t); // 2 second resolution
+    }
+
+    #[test] fn round_trip() {
+        for method in [Method::Stored, Method::Deflated].iter().copied() {
+            let modified = DosDateTime::from_unix(1_600_000_000);
+            let zip = Reader::new(write(method, modified)).unwrap();
+            let names = zip.entries().iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+            assert_eq!(names, ["META-INF/", "META-INF/MANIFEST.MF", "com/example/Empty.class", "com/example/Repeated.txt"]);
+
+            let dir = zip.find("META-INF/").unwrap();
+            assert!(dir.is_dir());
+            assert_eq!(dir.attributes, ATTRIBUTE_DIRECTORY);
+            assert_eq!(dir.extra, [0xFE, 0xCA, 0x00, 0x00]);
+
+            for entry in zip.entries() {
+                assert_eq!(entry.modified, modified);
+                assert_eq!(entry.flags, FLAG_UTF8);
+            }
+
+            let repeated = zip.find("com/example/Repeated.txt").unwrap();
+            assert_eq!(zip.data(repeated).unwrap(), "jerk ".repeat(1000).as_bytes());
+            assert_eq!(repeated.method, method as u16);
+            if method == Method::Deflated { assert!(repeated.compressed < repeated.uncompressed); }
+            assert_eq!(zip.data(zip.find("com/example/Empty.class").unwrap()).unwrap(), b"");
+        }
+    }
+
+    #[test] fn extended_timestamp() {
+        let modified = DosDateTime::from_unix(1_600_000_000);
+        let mut zip = Writer::new(Vec::new());
+        zip.directory("dos/", modified, &[]).unwrap();
+        zip.directory("other/", modified, &[0xFE, 0xCA, 0x00, 0x00]).unwrap();
+        zip.directory("extended/", modified, &[0xFE, 0xCA, 0x00, 0x00, 0x55, 0x54, 0x05, 0x00, 0x01, 0x01, 0x02, 0x03, 0x04]).unwrap();
+        zip.directory("no-mtime/", modified, &[0x55, 0x54, 0x05, 0x00, 0x02, 0x01, 0x02, 0x03, 0x04]).unwrap();
+        zip.directory("truncated/", modified, &[0x55, 0x54, 0x05, 0x00, 0x01, 0x01, 0x02]).unwrap();
+        let zip = Reader::new(zip.finish().unwrap()).unwrap();
+        let modified = |name| zip.find(name).unwrap().modified_unix();
+        assert_eq!(modified("dos/"),        1_600_000_000);
+        assert_eq!(modified("other/"),      1_600_000_000);
+        assert_eq!(modified("extended/"),   0x04030201);
+        assert_eq!(modified("no-mtime/"),   1_600_000_000);
+        assert_eq!(modified("truncated/"),  1_600_000_000);
+    }
+
+    #[test] fn reproducible() {
+        let modified = DosDateTime::from_unix(1_600_000_000);
+        let a = write(Method::Deflated, modified);
+        assert_eq!(a, write(Method::Deflated, modified));
+        assert_ne!(a, write(Method::Deflated, DosDateTime::MIN));
+
+        // Copying every entry reproduces the original archive exactly
+        let reader = Reader::new(a.clone()).unwrap();
+        let mut copy = Writer::new(Vec::new());
+        for entry in reader.entries() { copy.copy(&reader, entry).unwrap(); }
+        assert_eq!(copy.finish().unwrap(), a);
+    }
+
+    #[test] fn corrupt() {
+        let mut zip = write(Method::Stored, DosDateTime::MIN);
+        let pos = zip.windows(5).position(|w| w == b"jerk ").unwrap();
+        zip[pos] = b'J';
+        let zip = Reader::new(zip).unwrap();
+        let err = zip.data(zip.find("com/example/Repeated.txt").unwrap()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        assert!(Reader::new(b"not a zip".to_vec()).is_err());
+    }
+}